@@ -0,0 +1,219 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::error::{InterpreterError, Result};
+use crate::eval::{not_a, reserve, slice_bounds};
+use crate::symbol::Symbol;
+use crate::value::{Operation, Value};
+
+pub fn bytevector(value: &Value) -> Result<Rc<RefCell<Vec<u8>>>> {
+    match value {
+        Value::Bytevector(bytes) => Ok(bytes.clone()),
+        value => Err(not_a("bytevector", value)),
+    }
+}
+
+pub fn byte(value: &Value) -> Result<u8> {
+    match value {
+        Value::Int(byte) => u8::try_from(*byte).map_err(|_| not_a("byte", value)),
+        value => Err(not_a("byte", value)),
+    }
+}
+
+/// The bytes between the optional start and end index `arguments`.
+pub fn byte_slice<'a>(bytes: &'a [u8], arguments: &[Value]) -> Result<&'a [u8]> {
+    let (start, end) = slice_bounds(arguments, bytes.len())?;
+    Ok(&bytes[start..end])
+}
+
+/// The range of the `width` bytes starting at the index `value`.
+fn field(bytes: &[u8], value: &Value, width: usize) -> Result<std::ops::Range<usize>> {
+    let start = match value {
+        Value::Int(index) => usize::try_from(*index).ok(),
+        _ => None,
+    };
+    let end = start.and_then(|start| start.checked_add(width));
+    match (start, end) {
+        (Some(start), Some(end)) if end <= bytes.len() => Ok(start..end),
+        _ => Err(InterpreterError::ValueError(format!(
+            "index {} out of range for {} bytes of a bytevector of length {}",
+            value,
+            width,
+            bytes.len()
+        ))),
+    }
+}
+
+/// Whether the endianness symbol `value` means little-endian.
+fn is_little_endian(value: &Value) -> Result<bool> {
+    match value {
+        Value::Symbol(symbol) if *symbol == Symbol::intern("little") => Ok(true),
+        Value::Symbol(symbol) if *symbol == Symbol::intern("big") => Ok(false),
+        Value::Symbol(symbol) if *symbol == Symbol::intern("native") => {
+            Ok(cfg!(target_endian = "little"))
+        }
+        value => Err(not_a("endianness, one of big, little and native", value)),
+    }
+}
+
+/// The width in bytes and signedness of the integers a multi-byte accessor
+/// reads or writes.
+fn layout(operation: Operation) -> (usize, bool) {
+    match operation {
+        Operation::BytevectorU16Ref | Operation::BytevectorU16Set => (2, false),
+        Operation::BytevectorS16Ref | Operation::BytevectorS16Set => (2, true),
+        Operation::BytevectorU32Ref | Operation::BytevectorU32Set => (4, false),
+        Operation::BytevectorS32Ref | Operation::BytevectorS32Set => (4, true),
+        Operation::BytevectorU64Ref | Operation::BytevectorU64Set => (8, false),
+        _ => (8, true),
+    }
+}
+
+fn read_integer(bytes: &[u8], signed: bool, little_endian: bool) -> i128 {
+    let fold = |value: u128, byte: &u8| value << 8 | u128::from(*byte);
+    let unsigned = match little_endian {
+        true => bytes.iter().rev().fold(0, fold),
+        false => bytes.iter().fold(0, fold),
+    } as i128;
+    let bits = 8 * bytes.len();
+    match signed && unsigned >> (bits - 1) == 1 {
+        true => unsigned - (1 << bits),
+        false => unsigned,
+    }
+}
+
+fn write_integer(bytes: &mut [u8], value: i128, little_endian: bool) {
+    let width = bytes.len();
+    for index in 0..width {
+        let byte = (value >> (8 * index)) as u8;
+        match little_endian {
+            true => bytes[index] = byte,
+            false => bytes[width - 1 - index] = byte,
+        }
+    }
+}
+
+/// Applies the bytevector procedures.
+pub fn apply_bytevector_primitive(operation: Operation, arguments: &[Value]) -> Result<Value> {
+    match operation {
+        Operation::Bytevector => Ok(Value::bytevector(
+            arguments.iter().map(byte).collect::<Result<_>>()?,
+        )),
+        Operation::IsBytevector => Ok(Value::Bool(matches!(arguments[0], Value::Bytevector(_)))),
+        Operation::MakeBytevector => {
+            let Value::Int(length) = arguments[0] else {
+                return Err(not_a("length", &arguments[0]));
+            };
+            let length = usize::try_from(length).map_err(|_| not_a("length", &arguments[0]))?;
+            let fill = arguments.get(1).map(byte).transpose()?.unwrap_or(0);
+            let mut bytes = reserve(length)?;
+            bytes.resize(length, fill);
+            Ok(Value::bytevector(bytes))
+        }
+        Operation::BytevectorLength => {
+            Ok(Value::Int(bytevector(&arguments[0])?.borrow().len() as i128))
+        }
+        Operation::BytevectorU8Ref => {
+            let bytes = bytevector(&arguments[0])?;
+            let bytes = bytes.borrow();
+            let range = field(&bytes, &arguments[1], 1)?;
+            Ok(Value::Int(i128::from(bytes[range.start])))
+        }
+        Operation::BytevectorU8Set => {
+            let bytes = bytevector(&arguments[0])?;
+            let byte = byte(&arguments[2])?;
+            let mut bytes = bytes.borrow_mut();
+            let range = field(&bytes, &arguments[1], 1)?;
+            bytes[range.start] = byte;
+            Ok(Value::Nil)
+        }
+        Operation::BytevectorCopy => {
+            let bytes = bytevector(&arguments[0])?;
+            let bytes = bytes.borrow();
+            Ok(Value::bytevector(
+                byte_slice(&bytes, &arguments[1..])?.to_vec(),
+            ))
+        }
+        Operation::BytevectorAppend => {
+            let mut appended = Vec::new();
+            for argument in arguments {
+                appended.extend_from_slice(&bytevector(argument)?.borrow());
+            }
+            Ok(Value::bytevector(appended))
+        }
+        Operation::Utf8ToString => {
+            let bytes = bytevector(&arguments[0])?;
+            let bytes = bytes.borrow();
+            let string =
+                std::str::from_utf8(byte_slice(&bytes, &arguments[1..])?).map_err(|error| {
+                    InterpreterError::ValueError(format!("utf8->string: {}", error))
+                })?;
+            Ok(Value::string(string))
+        }
+        Operation::StringToUtf8 => {
+            let Value::Str(string) = &arguments[0] else {
+                return Err(not_a("string", &arguments[0]));
+            };
+            let string = string.borrow();
+            let (start, end) = slice_bounds(&arguments[1..], string.chars().count())?;
+            let slice = string
+                .chars()
+                .skip(start)
+                .take(end - start)
+                .collect::<String>();
+            Ok(Value::bytevector(slice.into_bytes()))
+        }
+        Operation::BytevectorU16Ref
+        | Operation::BytevectorS16Ref
+        | Operation::BytevectorU32Ref
+        | Operation::BytevectorS32Ref
+        | Operation::BytevectorU64Ref
+        | Operation::BytevectorS64Ref => {
+            let (width, signed) = layout(operation);
+            let bytes = bytevector(&arguments[0])?;
+            let bytes = bytes.borrow();
+            let range = field(&bytes, &arguments[1], width)?;
+            let little_endian = is_little_endian(&arguments[2])?;
+            Ok(Value::Int(read_integer(
+                &bytes[range],
+                signed,
+                little_endian,
+            )))
+        }
+        Operation::BytevectorU16Set
+        | Operation::BytevectorS16Set
+        | Operation::BytevectorU32Set
+        | Operation::BytevectorS32Set
+        | Operation::BytevectorU64Set
+        | Operation::BytevectorS64Set => {
+            let (width, signed) = layout(operation);
+            let bits = 8 * width as u32;
+            let (min, max) = match signed {
+                true => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+                false => (0, (1 << bits) - 1),
+            };
+            let value = match arguments[2] {
+                Value::Int(value) if (min..=max).contains(&value) => value,
+                _ => {
+                    return Err(InterpreterError::ValueError(format!(
+                        "{}: {} is not an integer between {} and {}",
+                        operation.name(),
+                        arguments[2],
+                        min,
+                        max
+                    )))
+                }
+            };
+            let little_endian = is_little_endian(&arguments[3])?;
+            let bytes = bytevector(&arguments[0])?;
+            let mut bytes = bytes.borrow_mut();
+            let range = field(&bytes, &arguments[1], width)?;
+            write_integer(&mut bytes[range], value, little_endian);
+            Ok(Value::Nil)
+        }
+        _ => Err(InterpreterError::RuntimeError(format!(
+            "{} is not a bytevector primitive",
+            operation.name()
+        ))),
+    }
+}
@@ -1,73 +1,95 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::error::{InterpreterError, Result};
-use crate::SymbolicExpression;
+use crate::symbol::{Symbol, SymbolMap};
+use crate::value::{Value, OPERATION_NAMES};
 
-type Bindings = HashMap<String, SymbolicExpression>;
+type Bindings = SymbolMap<Value>;
 type FrameLink = Rc<RefCell<Frame>>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 struct Frame {
     bindings: Bindings,
     outer: Option<FrameLink>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Env {
     current_frame: FrameLink,
 }
 
-#[derive(Debug, Clone)]
-pub struct VariableNotFoundError;
-
 impl Frame {
     fn new() -> Self {
         Self {
-            bindings: HashMap::new(),
+            bindings: Bindings::default(),
             outer: None,
         }
     }
 
     fn with_outer(outer: FrameLink) -> Self {
         Self {
-            bindings: HashMap::new(),
+            bindings: Bindings::default(),
             outer: Some(outer),
         }
     }
 
-    fn define_symbol(&mut self, symbol: &str, value: SymbolicExpression) {
-        self.bindings.insert(symbol.to_owned(), value);
+    fn define_symbol(&mut self, symbol: Symbol, value: Value) {
+        self.bindings.insert(symbol, value);
     }
 
-    fn find_symbol(&self, symbol: &str) -> Option<SymbolicExpression> {
-        self.bindings.get(symbol).cloned().or_else(|| {
+    fn find_symbol(&self, symbol: Symbol) -> Option<Value> {
+        self.bindings.get(&symbol).cloned().or_else(|| {
             self.outer
                 .as_ref()
                 .and_then(|outer| outer.borrow().find_symbol(symbol))
         })
     }
 
-    fn set_symbol(&mut self, symbol: &str, new_value: SymbolicExpression) -> Result<()> {
-        match self.bindings.get_mut(symbol) {
+    fn is_bound(&self, symbol: Symbol) -> bool {
+        self.bindings.contains_key(&symbol)
+            || self
+                .outer
+                .as_ref()
+                .is_some_and(|outer| outer.borrow().is_bound(symbol))
+    }
+
+    fn set_symbol(&mut self, symbol: Symbol, new_value: Value) -> Result<()> {
+        match self.bindings.get_mut(&symbol) {
             Some(value) => {
                 *value = new_value;
                 Ok(())
             }
             None => match self.outer.as_ref() {
                 Some(outer) => outer.borrow_mut().set_symbol(symbol, new_value),
-                None => Err(InterpreterError::VariableNotFound(symbol.to_string())),
+                None => Err(InterpreterError::VariableNotFound(
+                    symbol.name().to_string(),
+                )),
             },
         }
     }
 }
 
 impl Env {
+    /// A global environment with the primitive procedures bound. Special
+    /// forms aren't values, the evaluator recognizes them by name unless a
+    /// binding shadows them.
     pub fn new() -> Self {
-        Env {
+        let mut env = Env {
             current_frame: Rc::new(RefCell::new(Frame::new())),
+        };
+        for (name, operation) in OPERATION_NAMES {
+            if !operation.is_special_form() {
+                env.define_symbol(Symbol::intern(name), Value::Operation(operation));
+            }
         }
+        env
+    }
+
+    /// An environment without any bindings, only the special forms are
+    /// available in it.
+    pub fn empty() -> Self {
+        Env::with_frame(Frame::new())
     }
 
     fn with_frame(frame: Frame) -> Self {
@@ -81,33 +103,54 @@ impl Env {
         self.current_frame = Rc::new(RefCell::new(new_frame));
     }
 
-    pub fn pop_frame(&mut self) {
-        let new_current_frame = self.current_frame.borrow().outer.clone().expect(
-            "should have outer frame, seems like you are trying to remove the global frame",
-        );
-        self.current_frame = new_current_frame;
-    }
-
     pub fn get_lambda_env(&self) -> Env {
         let new_frame = Frame::with_outer(self.current_frame.clone());
         Env::with_frame(new_frame)
     }
 
-    pub fn find_symbol(&self, symbol: &str) -> Result<SymbolicExpression> {
-        self.current_frame
-            .borrow()
-            .find_symbol(symbol)
-            .ok_or(InterpreterError::VariableNotFound(symbol.to_string()))
+    /// The outermost frame, where top level definitions go.
+    pub fn global(&self) -> Env {
+        let mut frame = self.current_frame.clone();
+        loop {
+            let outer = frame.borrow().outer.clone();
+            match outer {
+                Some(outer) => frame = outer,
+                None => {
+                    return Env {
+                        current_frame: frame,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether both are the same environment rather than equal copies.
+    pub fn ptr_eq(&self, other: &Env) -> bool {
+        Rc::ptr_eq(&self.current_frame, &other.current_frame)
+    }
+
+    pub fn find_symbol(&self, symbol: Symbol) -> Result<Value> {
+        self.lookup(symbol)
+            .ok_or_else(|| InterpreterError::VariableNotFound(symbol.name().to_string()))
+    }
+
+    /// Like `find_symbol`, without building an error for unbound symbols.
+    pub fn lookup(&self, symbol: Symbol) -> Option<Value> {
+        self.current_frame.borrow().find_symbol(symbol)
+    }
+
+    pub fn is_bound(&self, symbol: Symbol) -> bool {
+        self.current_frame.borrow().is_bound(symbol)
     }
 
-    pub fn define_symbol(&mut self, symbol: &str, value: SymbolicExpression) {
+    pub fn define_symbol(&mut self, symbol: Symbol, value: Value) {
         self.current_frame
             .as_ref()
             .borrow_mut()
             .define_symbol(symbol, value);
     }
 
-    pub fn set_symbol(&mut self, symbol: &str, new_value: SymbolicExpression) -> Result<()> {
+    pub fn set_symbol(&mut self, symbol: Symbol, new_value: Value) -> Result<()> {
         self.current_frame
             .as_ref()
             .borrow_mut()
@@ -119,15 +162,16 @@ impl Env {
 mod tests {
     use super::Env;
     use crate::error::Result;
-    use crate::SymbolicExpression;
+    use crate::symbol::Symbol;
+    use crate::value::Value;
 
-    type SE = SymbolicExpression;
+    type SE = Value;
 
     #[test]
     fn global_frame() -> Result<()> {
         let mut global_env = Env::new();
 
-        let name = "a";
+        let name = Symbol::intern("a");
 
         global_env.define_symbol(name, SE::Nil);
         assert_eq!(global_env.find_symbol(name)?, SE::Nil);
@@ -140,39 +184,39 @@ mod tests {
     #[test]
     fn multiple_frames() -> Result<()> {
         let mut env = Env::new();
-        let a = "a";
-        let b = "b";
-        let c = "c";
+        let a = Symbol::intern("a");
+        let b = Symbol::intern("b");
+        let c = Symbol::intern("c");
 
         env.define_symbol(a, SE::Nil);
         assert_eq!(env.find_symbol(a)?, SE::Nil);
 
-        env.define_symbol(b, SE::Str("b1".to_string()));
-        assert_eq!(env.find_symbol(b)?, SE::Str("b1".to_string()));
+        env.define_symbol(b, SE::string("b1"));
+        assert_eq!(env.find_symbol(b)?, SE::string("b1"));
 
-        env.add_frame();
+        let mut inner_env = env.clone();
+        inner_env.add_frame();
 
-        env.define_symbol(a, SE::Int(2));
-        assert_eq!(env.find_symbol(a)?, SE::Int(2));
-
-        env.set_symbol(b, SE::Str("b2".to_string()))?;
-        assert_eq!(env.find_symbol(b)?, SE::Str("b2".to_string()));
+        inner_env.define_symbol(a, SE::Int(2));
+        assert_eq!(inner_env.find_symbol(a)?, SE::Int(2));
 
-        env.define_symbol(c, SE::Str("c".to_string()));
-        assert_eq!(env.find_symbol(c)?, SE::Str("c".to_string()));
+        inner_env.set_symbol(b, SE::string("b2"))?;
+        assert_eq!(inner_env.find_symbol(b)?, SE::string("b2"));
 
-        env.pop_frame();
+        inner_env.define_symbol(c, SE::string("c"));
+        assert_eq!(inner_env.find_symbol(c)?, SE::string("c"));
 
         assert_eq!(env.find_symbol(a)?, SE::Nil);
-        assert_eq!(env.find_symbol(b)?, SE::Str("b2".to_string()));
+        assert_eq!(env.find_symbol(b)?, SE::string("b2"));
         assert!(env.find_symbol(c).is_err());
         Ok(())
     }
 
     #[test]
     fn lambda_env() -> Result<()> {
-        let mut env = Env::new();
-        let a = "a";
+        let global_env = Env::new();
+        let mut env = global_env.clone();
+        let a = Symbol::intern("a");
 
         env.add_frame();
         env.define_symbol(a, SE::Int(1));
@@ -184,9 +228,8 @@ mod tests {
         assert_eq!(lambda_env.find_symbol(a)?, SE::Int(2));
         assert_eq!(env.find_symbol(a)?, SE::Int(2));
 
-        env.pop_frame();
         assert_eq!(lambda_env.find_symbol(a)?, SE::Int(2));
-        assert!(env.find_symbol(a).is_err());
+        assert!(global_env.find_symbol(a).is_err());
         Ok(())
     }
 }
@@ -1,17 +1,52 @@
 use std::error;
 use std::fmt;
 
-use crate::parse::SymbolicExpression;
+use crate::parse::Datum;
+use crate::value::Value;
 
 pub type Result<T> = std::result::Result<T, InterpreterError>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InterpreterError {
     VariableNotFound(String),
-    SyntaxError(SymbolicExpression),
+    SyntaxError(Datum),
     RuntimeError(String),
     ValueError(String),
     ArgumentError(String),
+    /// Opening, reading or writing a file or the console failed.
+    IoError(String),
+    /// `read` found something that isn't a datum.
+    ReadError(String),
+    /// A value raised with `raise` or `error` that no handler caught.
+    Raise(Value),
+}
+
+impl InterpreterError {
+    /// The explanation without the kind of the error, used as the message of
+    /// the error object a handler receives for it.
+    pub fn message(&self) -> String {
+        match self {
+            Self::VariableNotFound(name) => format!("variable {} not found", name),
+            Self::SyntaxError(exp) => format!("invalid syntax {}", exp),
+            Self::RuntimeError(explanation)
+            | Self::ValueError(explanation)
+            | Self::ArgumentError(explanation)
+            | Self::IoError(explanation)
+            | Self::ReadError(explanation) => explanation.clone(),
+            Self::Raise(value) => value.to_string(),
+        }
+    }
+
+    /// Turns a raised value back into the error it was created from, if any.
+    pub fn from_raised(value: Value) -> Self {
+        match &value {
+            Value::ErrorObject(error_object) => match &error_object.cause {
+                Some(cause) => cause.clone(),
+                None => Self::Raise(value),
+            },
+            _ => Self::Raise(value),
+        }
+    }
 }
 
 impl fmt::Display for InterpreterError {
@@ -24,6 +59,10 @@ impl fmt::Display for InterpreterError {
             Self::RuntimeError(explanation) => write!(f, "RuntimeError: {}", explanation),
             Self::ValueError(explanation) => write!(f, "ValueError: {}", explanation),
             Self::ArgumentError(explanation) => write!(f, "ArgumentError: {}", explanation),
+            Self::IoError(explanation) => write!(f, "IoError: {}", explanation),
+            Self::ReadError(explanation) => write!(f, "ReadError: {}", explanation),
+            Self::Raise(Value::ErrorObject(error_object)) => write!(f, "Error: {}", error_object),
+            Self::Raise(value) => write!(f, "Error: uncaught exception {}", value),
         }
     }
 }
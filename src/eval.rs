@@ -1,384 +1,2545 @@
 use crate::{
+    bytevectors::apply_bytevector_primitive,
     env::Env,
     error::{InterpreterError, Result},
-    parse::{Operation, SymbolicExpression},
+    expand::{expand, macroexpand},
+    format::apply_format,
+    lists::apply_list_primitive,
+    parse::Datum,
+    port::{apply_port_primitive, current_output, set_current_output, Port},
+    regexp::apply_regexp_primitive,
+    strings::apply_string_primitive,
+    symbol::Symbol,
+    value::{Arity, ErrorObject, Lambda, Operation, Parameters, Value},
 };
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
-fn eval_comparison_operation(
-    evaluated_arguments: Vec<SymbolicExpression>,
-    op: fn(&SymbolicExpression, &SymbolicExpression) -> bool,
-) -> SymbolicExpression {
-    let mut arg_iter = evaluated_arguments.iter();
-    let previous = arg_iter.next().unwrap();
+/// Marks the stack position a `call/ec` continuation escapes to.
+#[derive(Debug)]
+pub struct EscapePoint;
 
-    for current in arg_iter {
-        if !(op(previous, current)) {
-            return SymbolicExpression::Bool(false);
+/// Entry of the list of active `dynamic-wind`s. `before` has run when
+/// control entered it, `after` runs whenever control leaves.
+#[derive(Debug)]
+pub struct Winder {
+    before: Value,
+    after: Value,
+    parent: Option<Rc<Winder>>,
+    depth: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum WindAction {
+    Exit(Rc<Winder>),
+    Enter(Rc<Winder>),
+}
+
+/// Entry of the list of installed exception handlers.
+#[derive(Debug)]
+pub struct Handlers {
+    handler: Handler,
+    parent: Option<Rc<Handlers>>,
+}
+
+#[derive(Debug)]
+pub enum Handler {
+    /// Installed by `with-exception-handler`.
+    Procedure(Value),
+    /// Installed by `guard`, which catches by escaping to its frame.
+    Guard(Rc<EscapePoint>),
+}
+
+/// Where control goes once the `after` and `before` thunks between the
+/// current and the target `dynamic-wind`s have run.
+#[derive(Debug, Clone)]
+pub enum RewindTarget {
+    Continuation(Rc<Continuation>, Value),
+    Guard(Rc<EscapePoint>, Value),
+    Error(InterpreterError),
+}
+
+/// What is left to do once the current expression has produced a value.
+/// Frames only hold reference counted data, so copying the stack for a
+/// continuation is cheap.
+#[derive(Debug, Clone)]
+pub enum Frame {
+    /// Evaluates the operator and operands of a call onto the value stack,
+    /// starting at `base`.
+    Call {
+        expressions: Rc<[Datum]>,
+        base: usize,
+        env: Env,
+    },
+    If {
+        consequent: Option<Datum>,
+        alternative: Option<Datum>,
+        env: Env,
+    },
+    Cond {
+        clauses: Rc<[Datum]>,
+        index: usize,
+        env: Env,
+    },
+    Sequence {
+        expressions: Rc<[Datum]>,
+        index: usize,
+        env: Env,
+    },
+    Module {
+        expressions: Rc<[Datum]>,
+        index: usize,
+        env: Env,
+    },
+    Define {
+        name: Symbol,
+        env: Env,
+    },
+    Set {
+        name: Symbol,
+        env: Env,
+    },
+    Let {
+        bindings: Rc<[Datum]>,
+        index: usize,
+        name: Symbol,
+        /// The whole `let` form, its body starts at index 2.
+        body: Rc<[Datum]>,
+        env: Env,
+    },
+    /// Computes the default of a missing optional or key parameter.
+    Default {
+        lambda: Rc<Lambda>,
+        defaults: Rc<[(Symbol, Datum)]>,
+        index: usize,
+        env: Env,
+    },
+    /// Expands the macro use that the operand of `macroexpand` evaluated to.
+    MacroExpand {
+        once: bool,
+        env: Env,
+    },
+    Escape {
+        point: Rc<EscapePoint>,
+        values_height: usize,
+        winders: Option<Rc<Winder>>,
+        handlers: Option<Rc<Handlers>>,
+    },
+    /// Body of a `guard`, raised conditions escape to it.
+    Guard {
+        point: Rc<EscapePoint>,
+        clauses: Rc<[Datum]>,
+        env: Env,
+        values_height: usize,
+        winders: Option<Rc<Winder>>,
+        handlers: Option<Rc<Handlers>>,
+    },
+    GuardClause {
+        clauses: Rc<[Datum]>,
+        index: usize,
+        condition: Value,
+        env: Env,
+    },
+    /// Reinstalls the handlers that were active before the body of a
+    /// `with-exception-handler` or a handler call.
+    RestoreHandlers(Option<Rc<Handlers>>),
+    /// Waits for a handler called by `raise`, which must not return.
+    RaiseReturned(Value),
+    /// Waits for the `before` thunk of a `dynamic-wind`.
+    WindBefore {
+        before: Value,
+        thunk: Value,
+        after: Value,
+    },
+    /// Waits for the body of a `dynamic-wind` to leave it normally.
+    WindBody(Rc<Winder>),
+    /// Ignores the value it gets and returns its own instead.
+    Deliver(Value),
+    /// Ignores the value it gets and returns what was written to the port.
+    OutputString(Rc<Port>),
+    /// Closes the port of a `call-with-input-file` or the like once its
+    /// procedure returns.
+    ClosePort(Rc<Port>),
+    /// Calls `procedure` on the elements at `index` of the `columns` for
+    /// `map` and its variants, `results` is a list in reverse order.
+    Map {
+        procedure: Value,
+        columns: Columns,
+        index: usize,
+        results: Value,
+        collect: Collect,
+    },
+    /// Compares the element at `index` with the ones `kept` so far, from
+    /// the most recent one, with `remaining` left to compare against.
+    DeleteDuplicates {
+        procedure: Value,
+        elements: Rc<[Value]>,
+        index: usize,
+        kept: Value,
+        remaining: Value,
+    },
+    /// Waits for `procedure` to compare two elements of a merge sort.
+    Sort(Sort),
+    /// Calls `procedure` on the accumulated value and the elements at
+    /// `index` of the `columns` for the folds.
+    Fold {
+        procedure: Value,
+        columns: Columns,
+        index: usize,
+        accumulator_first: bool,
+    },
+    /// Runs the `after` and `before` thunks on the way to `target`, one
+    /// action at a time.
+    Rewind {
+        actions: Rc<[WindAction]>,
+        index: usize,
+        target: RewindTarget,
+    },
+}
+
+#[derive(Debug)]
+pub enum Continuation {
+    /// A copy of the whole stack, it can be resumed any number of times.
+    Full {
+        stack: Vec<Frame>,
+        values: Vec<Value>,
+        winders: Option<Rc<Winder>>,
+        handlers: Option<Rc<Handlers>>,
+    },
+    /// Only unwinds to its `call/ec`, so capturing it copies nothing, but it
+    /// can't be used once that `call/ec` has returned.
+    Escape(Rc<EscapePoint>),
+}
+
+/// The sequences a `map` or fold walks through in lockstep.
+pub type Columns = Rc<[Rc<[Value]>]>;
+
+/// A stable bottom-up merge sort, one comparison at a time. Each pass
+/// merges neighbouring runs of `width` elements of `source` into `output`.
+#[derive(Debug, Clone)]
+pub struct Sort {
+    procedure: Value,
+    source: Rc<[Value]>,
+    width: usize,
+    /// Start of the two runs being merged.
+    start: usize,
+    /// Next elements of the left and the right run.
+    left: usize,
+    right: usize,
+    /// The merged elements of this pass, in reverse order.
+    output: Value,
+    /// Stops after the first pass, to merge two sorted lists.
+    once: bool,
+    target: SortTarget,
+}
+
+/// What a sort returns its elements as.
+#[derive(Debug, Clone)]
+pub enum SortTarget {
+    List,
+    Vector,
+    /// Writes them back into a vector from an index.
+    InPlace(Rc<RefCell<Vec<Value>>>, usize),
+}
+
+impl Sort {
+    fn new(procedure: Value, source: Rc<[Value]>, target: SortTarget) -> Sort {
+        Sort {
+            procedure,
+            source,
+            width: 1,
+            start: 0,
+            left: 0,
+            right: 1,
+            output: Value::Nil,
+            once: false,
+            target,
         }
     }
 
-    SymbolicExpression::Bool(true)
-}
+    fn merge(procedure: Value, first: Vec<Value>, second: Vec<Value>) -> Sort {
+        let width = first.len();
+        let source = first.into_iter().chain(second).collect();
+        Sort {
+            width,
+            right: width,
+            once: true,
+            ..Sort::new(procedure, source, SortTarget::List)
+        }
+    }
 
-fn eval_operation<'a>(
-    env: &mut Env,
-    operation: Operation,
-    expression_iter: &mut impl DoubleEndedIterator<Item = &'a SymbolicExpression>,
-) -> Result<SymbolicExpression> {
-    let mut eval_w_env = |expression| eval(env, expression);
+    /// End of the left run and of the right run.
+    fn bounds(&self) -> (usize, usize) {
+        let length = self.source.len();
+        let middle = (self.start + self.width).min(length);
+        match self.once {
+            true => (middle, length),
+            false => (middle, (self.start + 2 * self.width).min(length)),
+        }
+    }
 
-    match operation {
-        Operation::Add => expression_iter
-            .map(eval_w_env)
-            .reduce(|acc, elem| match (acc?, elem?) {
-                (SymbolicExpression::Float(acc_value), SymbolicExpression::Float(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value + elem_value))
-                }
-                (SymbolicExpression::Float(acc_value), SymbolicExpression::Int(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value + elem_value as f64))
+    fn take(&mut self, index: usize) {
+        self.output = Value::Cons {
+            head: Rc::new(self.source[index].clone()),
+            tail: Rc::new(std::mem::replace(&mut self.output, Value::Nil)),
+        };
+    }
+
+    /// Takes elements until the next one needs a comparison, or the sort
+    /// is done. Returns the two elements to compare, right one first.
+    fn advance(&mut self) -> Result<Option<(Value, Value)>> {
+        loop {
+            if self.width >= self.source.len() && !self.once {
+                return Ok(None);
+            }
+            let (middle, end) = self.bounds();
+            match (self.left < middle, self.right < end) {
+                (true, true) => {
+                    let pair = (
+                        self.source[self.right].clone(),
+                        self.source[self.left].clone(),
+                    );
+                    return Ok(Some(pair));
                 }
-                (SymbolicExpression::Int(acc_value), SymbolicExpression::Float(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value as f64 + elem_value))
+                (true, false) => {
+                    self.take(self.left);
+                    self.left += 1;
                 }
-                (SymbolicExpression::Int(acc_value), SymbolicExpression::Int(elem_value)) => {
-                    Ok(SymbolicExpression::Int(acc_value + elem_value))
+                (false, true) => {
+                    self.take(self.right);
+                    self.right += 1;
                 }
-                _ => Err(InterpreterError::ValueError("wrong type for +".into())),
-            })
-            .unwrap(),
-        Operation::Substract => expression_iter
-            .map(eval_w_env)
-            .reduce(|acc, elem| match (acc?, elem?) {
-                (SymbolicExpression::Float(acc_value), SymbolicExpression::Float(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value - elem_value))
+                (false, false) if end < self.source.len() => {
+                    self.start = end;
+                    self.left = end;
+                    self.right = (end + self.width).min(self.source.len());
                 }
-                (SymbolicExpression::Float(acc_value), SymbolicExpression::Int(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value - elem_value as f64))
-                }
-                (SymbolicExpression::Int(acc_value), SymbolicExpression::Float(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value as f64 - elem_value))
+                (false, false) => {
+                    let mut merged = std::mem::replace(&mut self.output, Value::Nil).to_vec()?;
+                    merged.reverse();
+                    self.source = merged.into();
+                    if self.once {
+                        return Ok(None);
+                    }
+                    self.width *= 2;
+                    self.start = 0;
+                    self.left = 0;
+                    self.right = self.width.min(self.source.len());
                 }
-                (SymbolicExpression::Int(acc_value), SymbolicExpression::Int(elem_value)) => {
-                    Ok(SymbolicExpression::Int(acc_value - elem_value))
+            }
+        }
+    }
+
+    /// Takes the right element if it compared less than the left one,
+    /// which keeps equal elements in order.
+    fn compared(&mut self, right_is_less: bool) {
+        if right_is_less {
+            self.take(self.right);
+            self.right += 1;
+        } else {
+            self.take(self.left);
+            self.left += 1;
+        }
+    }
+
+    fn finish(self) -> Value {
+        match self.target {
+            SortTarget::List => Value::list(self.source.iter().cloned()),
+            SortTarget::Vector => Value::vector(self.source.to_vec()),
+            SortTarget::InPlace(vector, start) => {
+                let mut vector = vector.borrow_mut();
+                for (offset, value) in self.source.iter().enumerate() {
+                    if let Some(slot) = vector.get_mut(start + offset) {
+                        *slot = value.clone();
+                    }
                 }
-                _ => Err(InterpreterError::ValueError("wrong type for -".into())),
+                Value::Nil
+            }
+        }
+    }
+}
+
+/// What a `map` variant builds from the results. The predicate based list
+/// procedures are `map`s too, over the elements of their last column.
+#[derive(Debug, Clone)]
+pub enum Collect {
+    List,
+    Vector,
+    String,
+    Nothing,
+    AppendMap,
+    FilterMap,
+    /// The elements the procedure returned true for, or false for.
+    Filter(bool),
+    /// A list of the elements the procedure returned true for and a list
+    /// of the others.
+    Partition,
+    Count,
+    /// The first element the procedure returned true for, `#f` if none.
+    Find,
+    /// The first tail starting with such an element, `#f` if none.
+    FindTail,
+    /// The entry at the index of the first such element.
+    Assoc(Rc<[Value]>),
+    /// The first true result, `#f` if none.
+    Any,
+    /// The last result, unless one is `#f`.
+    Every,
+    /// `#t` unless a result is true.
+    NotAny,
+    /// The index of the first true result, `#f` if none.
+    Index,
+}
+
+enum State {
+    Eval(Datum, Env),
+    Return(Value),
+}
+
+/// Evaluates with an explicit stack of frames instead of recursing on the
+/// Rust stack, which is what lets `call/cc` capture and re-enter the rest
+/// of a computation.
+struct Machine {
+    stack: Vec<Frame>,
+    /// Evaluated operators and operands of the calls in progress, shared so
+    /// that calls don't allocate an argument vector each.
+    values: Vec<Value>,
+    /// The `dynamic-wind`s control is currently in, innermost first.
+    winders: Option<Rc<Winder>>,
+    /// The installed exception handlers, innermost first.
+    handlers: Option<Rc<Handlers>>,
+    /// The top level of the environment the machine was started in, what
+    /// `interaction-environment` returns.
+    global: Env,
+}
+
+fn winder_depth(winders: &Option<Rc<Winder>>) -> usize {
+    winders.as_ref().map_or(0, |winder| winder.depth)
+}
+
+/// Lists the `after` thunks to run when leaving `from` and the `before`
+/// thunks to run when entering `to`, in the order they have to run.
+fn wind_actions(from: &Option<Rc<Winder>>, to: &Option<Rc<Winder>>) -> Vec<WindAction> {
+    let mut exits = Vec::new();
+    let mut entries = Vec::new();
+    let (mut from, mut to) = (from.clone(), to.clone());
+    loop {
+        match (&from, &to) {
+            (Some(left), Some(right)) if Rc::ptr_eq(left, right) => break,
+            (None, None) => break,
+            _ => {}
+        }
+        if winder_depth(&from) >= winder_depth(&to) {
+            if let Some(winder) = from {
+                from = winder.parent.clone();
+                exits.push(WindAction::Exit(winder));
+            }
+        } else if let Some(winder) = to {
+            to = winder.parent.clone();
+            entries.push(WindAction::Enter(winder));
+        }
+    }
+    exits.extend(entries.into_iter().rev());
+    exits
+}
+
+fn eval_comparison_operation(arguments: &[Value], op: fn(&Value, &Value) -> bool) -> Value {
+    Value::Bool(arguments.windows(2).all(|pair| op(&pair[0], &pair[1])))
+}
+
+fn eval_arithmetic_operation(
+    arguments: &[Value],
+    name: &str,
+    int_op: fn(i128, i128) -> Option<Value>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Value> {
+    let mut arguments = arguments.iter().cloned();
+    let first = arguments
+        .next()
+        .ok_or(InterpreterError::ArgumentError(format!(
+            "missing arguments for {}",
+            name
+        )))?;
+    arguments.try_fold(first, |acc, elem| match (acc, elem) {
+        (Value::Float(acc_value), Value::Float(elem_value)) => {
+            Ok(Value::Float(float_op(acc_value, elem_value)))
+        }
+        (Value::Float(acc_value), Value::Int(elem_value)) => {
+            Ok(Value::Float(float_op(acc_value, elem_value as f64)))
+        }
+        (Value::Int(acc_value), Value::Float(elem_value)) => {
+            Ok(Value::Float(float_op(acc_value as f64, elem_value)))
+        }
+        (Value::Int(acc_value), Value::Int(elem_value)) => int_op(acc_value, elem_value)
+            .ok_or_else(|| InterpreterError::RuntimeError(format!("integer overflow in {}", name))),
+        _ => Err(InterpreterError::ValueError(format!(
+            "wrong type for {}",
+            name
+        ))),
+    })
+}
+
+fn check_arity(name: &str, (min, max): Arity, count: usize) -> Result<()> {
+    if count >= min && max.is_none_or(|max| count <= max) {
+        return Ok(());
+    }
+    let plural = |count: usize| if count == 1 { "" } else { "s" };
+    let expected = match max {
+        Some(max) if max == min => format!("{} argument{}", min, plural(min)),
+        Some(max) => format!("between {} and {} arguments", min, max),
+        None => format!("at least {} argument{}", min, plural(min)),
+    };
+    Err(InterpreterError::ArgumentError(format!(
+        "{} expects {}, got {}",
+        name, expected, count
+    )))
+}
+
+/// Names a lambda after the first variable it is bound to.
+fn name_procedure(value: &Value, name: Symbol) {
+    if let Value::Lambda(lambda) = value {
+        if lambda.name.get().is_none() {
+            lambda.name.set(Some(name));
+        }
+    }
+}
+
+pub fn not_a(kind: &str, value: &Value) -> InterpreterError {
+    InterpreterError::ValueError(format!("{} is not a {}", value, kind))
+}
+
+pub fn file_name(value: &Value) -> Result<String> {
+    match value {
+        Value::Str(name) => Ok(name.borrow().clone()),
+        value => Err(not_a("file name", value)),
+    }
+}
+
+fn vector_items(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Vector(values) => Ok(values.borrow().clone()),
+        value => Err(not_a("vector", value)),
+    }
+}
+
+fn string_chars(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Str(string) => Ok(string.borrow().chars().map(Value::Char).collect()),
+        value => Err(not_a("string", value)),
+    }
+}
+
+/// An empty vector with room for `length` elements, an error where
+/// allocating them would abort the interpreter.
+pub fn reserve<T>(length: usize) -> Result<Vec<T>> {
+    let mut elements = Vec::new();
+    elements.try_reserve_exact(length).map_err(|_| {
+        InterpreterError::ValueError(format!("cannot allocate {} elements", length))
+    })?;
+    Ok(elements)
+}
+
+/// The optional start and end index arguments of a procedure on a slice
+/// of a sequence of `length` elements.
+pub fn slice_bounds(arguments: &[Value], length: usize) -> Result<(usize, usize)> {
+    let index = |argument: Option<&Value>, default: usize| match argument {
+        None => Ok(default),
+        Some(Value::Int(index)) => usize::try_from(*index)
+            .ok()
+            .filter(|index| *index <= length)
+            .ok_or_else(|| InterpreterError::ValueError(format!("index {} out of range", index))),
+        Some(value) => Err(not_a("index", value)),
+    };
+    let start = index(arguments.first(), 0)?;
+    let end = index(arguments.get(1), length)?;
+    match start <= end {
+        true => Ok((start, end)),
+        false => Err(InterpreterError::ValueError(format!(
+            "start {} is after end {}",
+            start, end
+        ))),
+    }
+}
+
+/// Lists the elements of a sequence argument.
+type Items = fn(&Value) -> Result<Vec<Value>>;
+
+/// Converts the sequence arguments of a `map` or fold, cutting them to the
+/// length of the shortest.
+fn columns(sequences: &[Value], items: Items) -> Result<Columns> {
+    let mut columns = sequences.iter().map(items).collect::<Result<Vec<_>>>()?;
+    let length = columns.iter().map(Vec::len).min().unwrap_or(0);
+    Ok(columns
+        .iter_mut()
+        .map(|column| column.drain(..length).collect())
+        .collect())
+}
+
+/// Builds what a `map` variant returns from its results in reverse order.
+/// The value a `map` variant returns before the end of its columns, as
+/// soon as a result decides it.
+fn early_result(
+    collect: &Collect,
+    columns: &Columns,
+    index: usize,
+    result: &Value,
+) -> Option<Value> {
+    let elements = columns.last()?;
+    match (collect, result.is_true()) {
+        (Collect::Find, true) => Some(elements[index].clone()),
+        (Collect::FindTail, true) => Some(Value::list(elements[index..].iter().cloned())),
+        (Collect::Assoc(entries), true) => Some(entries[index].clone()),
+        (Collect::Any, true) => Some(result.clone()),
+        (Collect::Every, false) => Some(Value::Bool(false)),
+        (Collect::NotAny, true) => Some(Value::Bool(false)),
+        (Collect::Index, true) => Some(Value::Int(index as i128)),
+        _ => None,
+    }
+}
+
+fn collect_results(results: Value, collect: Collect, columns: &Columns) -> Result<Value> {
+    let mut results = results.to_vec()?;
+    results.reverse();
+    let elements = columns.last().cloned().unwrap_or_else(|| Rc::new([]));
+    let selected = |keep: bool| {
+        let chosen = results.iter().zip(elements.iter());
+        let chosen = chosen.filter(move |(result, _)| result.is_true() == keep);
+        Value::list(
+            chosen
+                .map(|(_, element)| element.clone())
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    };
+    match collect {
+        Collect::Filter(keep) => Ok(selected(keep)),
+        Collect::Partition => Ok(Value::list([selected(true), selected(false)].into_iter())),
+        Collect::Count => Ok(Value::Int(
+            results.iter().filter(|result| result.is_true()).count() as i128,
+        )),
+        Collect::Find | Collect::FindTail | Collect::Assoc(_) | Collect::Any | Collect::Index => {
+            Ok(Value::Bool(false))
+        }
+        Collect::Every => Ok(results.pop().unwrap_or(Value::Bool(true))),
+        Collect::NotAny => Ok(Value::Bool(true)),
+        Collect::AppendMap => apply_list_primitive(Operation::Append, &results),
+        Collect::FilterMap => Ok(Value::list(
+            results
+                .into_iter()
+                .filter(Value::is_true)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )),
+        Collect::List => Ok(Value::list(results.into_iter())),
+        Collect::Vector => Ok(Value::vector(results)),
+        Collect::String => results
+            .iter()
+            .map(|result| match result {
+                Value::Char(char) => Ok(*char),
+                value => Err(not_a("character", value)),
             })
-            .unwrap(),
-        Operation::Multiply => expression_iter
-            .map(eval_w_env)
-            .reduce(|acc, elem| match (acc?, elem?) {
-                (SymbolicExpression::Float(acc_value), SymbolicExpression::Float(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value * elem_value))
+            .collect::<Result<String>>()
+            .map(Value::string),
+        Collect::Nothing => Ok(Value::Nil),
+    }
+}
+
+fn missing_arguments() -> InterpreterError {
+    InterpreterError::ArgumentError("missing arguments".into())
+}
+
+fn apply_primitive(operation: Operation, arguments: &[Value]) -> Result<Value> {
+    match operation {
+        Operation::Add => eval_arithmetic_operation(
+            arguments,
+            "+",
+            |left, right| left.checked_add(right).map(Value::Int),
+            |left, right| left + right,
+        ),
+        Operation::Substract => eval_arithmetic_operation(
+            arguments,
+            "-",
+            |left, right| left.checked_sub(right).map(Value::Int),
+            |left, right| left - right,
+        ),
+        Operation::Multiply => eval_arithmetic_operation(
+            arguments,
+            "*",
+            |left, right| left.checked_mul(right).map(Value::Int),
+            |left, right| left * right,
+        ),
+        Operation::Divide => eval_arithmetic_operation(
+            arguments,
+            "/",
+            |left, right| Some(Value::Float(left as f64 / right as f64)),
+            |left, right| left / right,
+        ),
+        Operation::Exp => match arguments.first() {
+            Some(Value::Float(value)) => Ok(Value::Float(value.exp())),
+            Some(Value::Int(value)) => Ok(Value::Float((*value as f64).exp())),
+            Some(value) => Err(InterpreterError::RuntimeError(format!("exp on {}", value))),
+            None => Err(missing_arguments()),
+        },
+        Operation::Pow => match arguments {
+            [Value::Float(first), Value::Float(second), ..] => {
+                Ok(Value::Float(first.powf(*second)))
+            }
+            [Value::Float(first), Value::Int(second), ..] => {
+                Ok(Value::Float(first.powf(*second as f64)))
+            }
+            [Value::Int(first), Value::Float(second), ..] => {
+                Ok(Value::Float((*first as f64).powf(*second)))
+            }
+            [Value::Int(first), Value::Int(second), ..] => {
+                if *second < 0 {
+                    Ok(Value::Float((*first as f64).powf(*second as f64)))
+                } else {
+                    u32::try_from(*second)
+                        .ok()
+                        .and_then(|second| first.checked_pow(second))
+                        .map(Value::Int)
+                        .ok_or_else(|| {
+                            InterpreterError::RuntimeError("integer overflow in pow".into())
+                        })
                 }
-                (SymbolicExpression::Float(acc_value), SymbolicExpression::Int(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value * elem_value as f64))
+            }
+            [_, _, ..] => Err(InterpreterError::ValueError("wrong types for pow".into())),
+            _ => Err(missing_arguments()),
+        },
+        Operation::Cons => match arguments {
+            [head, tail, ..] => Ok(Value::Cons {
+                head: Rc::new(head.clone()),
+                tail: Rc::new(tail.clone()),
+            }),
+            _ => Err(missing_arguments()),
+        },
+        Operation::List => Ok(Value::list(arguments.iter().cloned())),
+        Operation::Vector => Ok(Value::vector(arguments.to_vec())),
+        Operation::VectorRef => match arguments {
+            [Value::Vector(values), Value::Int(index)] => usize::try_from(*index)
+                .ok()
+                .and_then(|index| values.borrow().get(index).cloned())
+                .ok_or_else(|| {
+                    InterpreterError::ValueError(format!("vector index {} out of range", index))
+                }),
+            _ => Err(InterpreterError::ValueError(
+                "vector-ref takes a vector and an index".into(),
+            )),
+        },
+        Operation::VectorLength => match arguments.first() {
+            Some(Value::Vector(values)) => Ok(Value::Int(values.borrow().len() as i128)),
+            Some(value) => Err(not_a("vector", value)),
+            None => Err(missing_arguments()),
+        },
+        Operation::VectorToList => match arguments.first() {
+            Some(Value::Vector(values)) => Ok(Value::list(values.borrow().iter().cloned())),
+            Some(value) => Err(not_a("vector", value)),
+            None => Err(missing_arguments()),
+        },
+        Operation::ListToVector => match arguments.first() {
+            Some(list) => Ok(Value::vector(list.to_vec()?)),
+            None => Err(missing_arguments()),
+        },
+        Operation::Append
+        | Operation::Reverse
+        | Operation::Length
+        | Operation::ListTail
+        | Operation::ListRef
+        | Operation::ListCopy
+        | Operation::LastPair
+        | Operation::Iota
+        | Operation::Take
+        | Operation::Drop
+        | Operation::Delete
+        | Operation::DeleteDuplicates
+        | Operation::Assoc
+        | Operation::Assq
+        | Operation::Assv
+        | Operation::Member
+        | Operation::Memq
+        | Operation::Memv => apply_list_primitive(operation, arguments),
+        Operation::String
+        | Operation::MakeString
+        | Operation::StringLength
+        | Operation::StringRef
+        | Operation::Substring
+        | Operation::StringAppend
+        | Operation::StringCopy
+        | Operation::StringToList
+        | Operation::ListToString
+        | Operation::StringUpcase
+        | Operation::StringDowncase
+        | Operation::StringIndex
+        | Operation::StringContains
+        | Operation::StringSplit
+        | Operation::StringJoin
+        | Operation::StringTrim
+        | Operation::StringTrimRight
+        | Operation::StringTrimBoth
+        | Operation::StringEq
+        | Operation::StringLess
+        | Operation::StringGreater
+        | Operation::StringLessOrEqual
+        | Operation::StringGreaterOrEqual
+        | Operation::StringSet
+        | Operation::StringFill => apply_string_primitive(operation, arguments),
+        Operation::Regexp
+        | Operation::IsRegexp
+        | Operation::RegexpMatch
+        | Operation::RegexpMatchPositions
+        | Operation::RegexpReplace
+        | Operation::RegexpReplaceAll
+        | Operation::RegexpSplit => apply_regexp_primitive(operation, arguments),
+        Operation::Bytevector
+        | Operation::IsBytevector
+        | Operation::MakeBytevector
+        | Operation::BytevectorLength
+        | Operation::BytevectorU8Ref
+        | Operation::BytevectorU8Set
+        | Operation::BytevectorCopy
+        | Operation::BytevectorAppend
+        | Operation::Utf8ToString
+        | Operation::StringToUtf8
+        | Operation::BytevectorU16Ref
+        | Operation::BytevectorU16Set
+        | Operation::BytevectorS16Ref
+        | Operation::BytevectorS16Set
+        | Operation::BytevectorU32Ref
+        | Operation::BytevectorU32Set
+        | Operation::BytevectorS32Ref
+        | Operation::BytevectorS32Set
+        | Operation::BytevectorU64Ref
+        | Operation::BytevectorU64Set
+        | Operation::BytevectorS64Ref
+        | Operation::BytevectorS64Set => apply_bytevector_primitive(operation, arguments),
+        Operation::OpenOutputString
+        | Operation::OpenInputString
+        | Operation::GetOutputString
+        | Operation::Display
+        | Operation::Write
+        | Operation::WriteShared
+        | Operation::WriteSimple
+        | Operation::WriteChar
+        | Operation::WriteString
+        | Operation::Newline
+        | Operation::FlushOutputPort
+        | Operation::CurrentOutputPort
+        | Operation::ReadChar
+        | Operation::PeekChar
+        | Operation::ReadLine
+        | Operation::ReadString
+        | Operation::Read
+        | Operation::IsCharReady
+        | Operation::OpenInputFile
+        | Operation::OpenOutputFile
+        | Operation::ClosePort
+        | Operation::OpenInputBytevector
+        | Operation::OpenOutputBytevector
+        | Operation::GetOutputBytevector
+        | Operation::OpenBinaryInputFile
+        | Operation::OpenBinaryOutputFile
+        | Operation::ReadU8
+        | Operation::PeekU8
+        | Operation::IsU8Ready
+        | Operation::ReadBytevector
+        | Operation::WriteU8
+        | Operation::WriteBytevector
+        | Operation::EofObject
+        | Operation::IsEofObject => apply_port_primitive(operation, arguments),
+        Operation::CharUpcase => match arguments.first() {
+            Some(Value::Char(char)) => Ok(Value::Char(char.to_uppercase().next().unwrap_or(*char))),
+            Some(value) => Err(not_a("character", value)),
+            None => Err(missing_arguments()),
+        },
+        Operation::Car => match arguments.first() {
+            Some(Value::Cons { head, .. }) => Ok(head.as_ref().clone()),
+            Some(value) => Err(InterpreterError::ValueError(format!(
+                "car on non cons type {}",
+                value
+            ))),
+            None => Err(missing_arguments()),
+        },
+        Operation::Cdr => match arguments.first() {
+            Some(Value::Cons { tail, .. }) => Ok(tail.as_ref().clone()),
+            Some(value) => Err(InterpreterError::ValueError(format!(
+                "cdr on non cons type {}",
+                value
+            ))),
+            None => Err(missing_arguments()),
+        },
+        Operation::Eq => Ok(eval_comparison_operation(arguments, |left, right| {
+            left == right
+        })),
+        Operation::Smaller => Ok(eval_comparison_operation(arguments, |left, right| {
+            left < right
+        })),
+        Operation::SmallerOrEqual => Ok(eval_comparison_operation(arguments, |left, right| {
+            left <= right
+        })),
+        Operation::Greater => Ok(eval_comparison_operation(arguments, |left, right| {
+            left > right
+        })),
+        Operation::GreaterOrEqual => Ok(eval_comparison_operation(arguments, |left, right| {
+            left >= right
+        })),
+        Operation::StringToSymbol => match arguments.first() {
+            Some(Value::Str(name)) => Ok(Value::Symbol(Symbol::intern(&name.borrow()))),
+            Some(value) => Err(InterpreterError::ValueError(format!(
+                "string->symbol on non string {}",
+                value
+            ))),
+            None => Err(missing_arguments()),
+        },
+        Operation::SymbolToString => match arguments.first() {
+            Some(Value::Symbol(symbol)) => Ok(Value::string(symbol.name().as_ref())),
+            Some(value) => Err(InterpreterError::ValueError(format!(
+                "symbol->string on non symbol {}",
+                value
+            ))),
+            None => Err(missing_arguments()),
+        },
+        Operation::SymbolEq => {
+            let symbols = arguments
+                .iter()
+                .map(|value| match value {
+                    Value::Symbol(symbol) => Ok(*symbol),
+                    value => Err(InterpreterError::ValueError(format!(
+                        "symbol=? on non symbol {}",
+                        value
+                    ))),
+                })
+                .collect::<Result<Vec<Symbol>>>()?;
+            Ok(Value::Bool(
+                symbols.windows(2).all(|pair| pair[0] == pair[1]),
+            ))
+        }
+        Operation::Gensym => {
+            let prefix = match arguments.first() {
+                None => "g".into(),
+                Some(Value::Str(prefix)) => prefix.borrow().as_str().into(),
+                Some(Value::Symbol(prefix)) => prefix.name(),
+                Some(value) => {
+                    return Err(InterpreterError::ValueError(format!(
+                        "gensym prefix has to be string or symbol, got {}",
+                        value
+                    )))
                 }
-                (SymbolicExpression::Int(acc_value), SymbolicExpression::Float(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value as f64 * elem_value))
+            };
+            Ok(Value::Symbol(Symbol::gensym(&prefix)))
+        }
+        Operation::IsErrorObject => match arguments.first() {
+            Some(value) => Ok(Value::Bool(matches!(value, Value::ErrorObject(_)))),
+            None => Err(missing_arguments()),
+        },
+        Operation::SchemeReportEnvironment | Operation::NullEnvironment => {
+            match arguments[0] {
+                Value::Int(5) => {}
+                _ => {
+                    return Err(InterpreterError::ValueError(format!(
+                        "{}: only version 5 is supported, got {}",
+                        operation.name(),
+                        arguments[0]
+                    )))
                 }
-                (SymbolicExpression::Int(acc_value), SymbolicExpression::Int(elem_value)) => {
-                    Ok(SymbolicExpression::Int(acc_value * elem_value))
+            }
+            Ok(Value::Environment(match operation {
+                Operation::SchemeReportEnvironment => Env::new(),
+                _ => Env::empty(),
+            }))
+        }
+        Operation::RestrictedEnvironment => restricted_environment(arguments),
+        Operation::Format => apply_format(arguments),
+        Operation::IsEnvironment => Ok(Value::Bool(matches!(arguments[0], Value::Environment(_)))),
+        Operation::IsReadError | Operation::IsFileError => Ok(Value::Bool(match &arguments[0] {
+            Value::ErrorObject(error_object) => matches!(
+                (operation, &error_object.cause),
+                (Operation::IsReadError, Some(InterpreterError::ReadError(_)))
+                    | (Operation::IsFileError, Some(InterpreterError::IoError(_)))
+            ),
+            _ => false,
+        })),
+        Operation::ErrorObjectMessage => match arguments.first() {
+            Some(Value::ErrorObject(error_object)) => {
+                Ok(Value::string(error_object.message.as_ref()))
+            }
+            Some(value) => Err(InterpreterError::ValueError(format!(
+                "error-object-message on non error object {}",
+                value
+            ))),
+            None => Err(missing_arguments()),
+        },
+        Operation::ErrorObjectIrritants => match arguments.first() {
+            Some(Value::ErrorObject(error_object)) => Ok(error_object.irritants.clone()),
+            Some(value) => Err(InterpreterError::ValueError(format!(
+                "error-object-irritants on non error object {}",
+                value
+            ))),
+            None => Err(missing_arguments()),
+        },
+        _ => Err(InterpreterError::RuntimeError(format!(
+            "{:?} is not a primitive procedure",
+            operation
+        ))),
+    }
+}
+
+/// A fresh environment with only the bindings `arguments[0]` names, taken
+/// from the environment `arguments[1]` or the built-in procedures. Special
+/// forms are always available, definitions made in it stay in it.
+fn restricted_environment(arguments: &[Value]) -> Result<Value> {
+    let source = match arguments.get(1) {
+        Some(Value::Environment(env)) => env.clone(),
+        Some(value) => return Err(not_a("environment", value)),
+        None => Env::new(),
+    };
+    let mut env = Env::empty();
+    for name in arguments[0].to_vec()? {
+        let Value::Symbol(name) = name else {
+            return Err(not_a("symbol", &name));
+        };
+        if special_form(name, &source).is_none() {
+            env.define_symbol(name, source.find_symbol(name)?);
+        }
+    }
+    Ok(Value::Environment(env))
+}
+
+fn make_error_object(arguments: &[Value]) -> Result<Value> {
+    match arguments {
+        [Value::Str(message), irritants @ ..] => Ok(Value::ErrorObject(Rc::new(ErrorObject {
+            message: message.borrow().as_str().into(),
+            irritants: irritants.iter().rfold(Value::Nil, |acc, elem| Value::Cons {
+                head: Rc::new(elem.clone()),
+                tail: Rc::new(acc),
+            }),
+            cause: None,
+        }))),
+        [message, ..] => Err(InterpreterError::ValueError(format!(
+            "error message has to be a string, got {}",
+            message
+        ))),
+        [] => Err(missing_arguments()),
+    }
+}
+
+fn dot() -> Datum {
+    Datum::Symbol(Symbol::intern("."))
+}
+
+/// Parses a parameter list like `(a #!optional (b 1) . rest)`. Required
+/// parameters come first, then the `#!optional`, `#!rest` (or `.`) and
+/// `#!key` sections, each of them at most once and in this order. Optional
+/// and key parameters without a default are `#f` when missing.
+fn lambda_parameters(definition: &[Datum]) -> Result<Parameters> {
+    let invalid = |reason: &str| {
+        InterpreterError::ArgumentError(format!(
+            "{} in lambda parameters {}",
+            reason,
+            Datum::List(definition.into())
+        ))
+    };
+    let with_default = |each: &Datum| match each {
+        Datum::Symbol(name) => Ok((*name, Datum::Bool(false))),
+        Datum::List(spec) => match spec.as_ref() {
+            [Datum::Symbol(name), default] => Ok((*name, default.clone())),
+            _ => Err(invalid("invalid default")),
+        },
+        _ => Err(invalid("non symbol parameter")),
+    };
+    let mut parameters = Parameters::default();
+    let mut section = 0;
+    for each in definition {
+        let marker = match each {
+            Datum::Symbol(symbol) => match symbol.name().as_ref() {
+                "#!optional" => Some(1),
+                "#!rest" | "." => Some(2),
+                "#!key" => Some(3),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(marker) = marker {
+            if marker <= section || (section == 2 && parameters.rest.is_none()) {
+                return Err(invalid("misplaced marker"));
+            }
+            section = marker;
+            continue;
+        }
+        match (section, each) {
+            (0, Datum::Symbol(name)) => parameters.required.push(*name),
+            (1, _) => parameters.optional.push(with_default(each)?),
+            (2, Datum::Symbol(name)) if parameters.rest.is_none() => parameters.rest = Some(*name),
+            (2, _) => return Err(invalid("exactly one rest parameter has to follow #!rest")),
+            (3, _) => parameters.keys.push(with_default(each)?),
+            _ => return Err(invalid("non symbol parameter")),
+        }
+    }
+    if section == 2 && parameters.rest.is_none() {
+        return Err(invalid("missing rest parameter"));
+    }
+    Ok(parameters)
+}
+
+fn make_lambda(parameters: &[Datum], body: &[Datum], env: &Env) -> Result<Rc<Lambda>> {
+    let parameters = lambda_parameters(parameters)?;
+    if body.is_empty() {
+        return Err(InterpreterError::ArgumentError(
+            "missing body for lambda".into(),
+        ));
+    }
+    Ok(Rc::new(Lambda {
+        name: Cell::new(None),
+        parameters,
+        env: env.get_lambda_env(),
+        body: body.into(),
+    }))
+}
+
+/// Binds the arguments that were passed by keyword.
+fn bind_keywords(
+    parameters: &Parameters,
+    arguments: &[Value],
+    env: &mut Env,
+    missing: &mut Vec<(Symbol, Datum)>,
+) -> Result<()> {
+    let mut bound = vec![false; parameters.keys.len()];
+    for pair in arguments.chunks(2) {
+        let (name, value) = match pair {
+            [Value::Keyword(name), value] => (*name, value),
+            _ => {
+                return Err(InterpreterError::ArgumentError(format!(
+                    "expected keyword arguments, got {}",
+                    pair[0]
+                )))
+            }
+        };
+        match parameters.keys.iter().position(|(key, _)| *key == name) {
+            Some(index) => {
+                bound[index] = true;
+                env.define_symbol(name, value.clone());
+            }
+            None if parameters.rest.is_some() => {}
+            None => {
+                return Err(InterpreterError::ArgumentError(format!(
+                    "unknown keyword argument {}:",
+                    name
+                )))
+            }
+        }
+    }
+    missing.extend(
+        parameters
+            .keys
+            .iter()
+            .zip(bound)
+            .filter(|(_, bound)| !bound)
+            .map(|(key, _)| key.clone()),
+    );
+    Ok(())
+}
+
+fn is_definition(form: &[Datum], env: &Env) -> bool {
+    matches!(form.first(), Some(Datum::Symbol(head)) if special_form(*head, env) == Some(Operation::Define))
+}
+
+/// Binds the names of the `define`s a body starts with before any of it
+/// runs, so they shadow outer bindings from the start like `letrec*`. Using
+/// one before its definition ran is an error.
+fn declare_definitions(body: &[Datum], env: &mut Env) {
+    for form in body {
+        let name = match form {
+            Datum::List(form) if is_definition(form, env) => match form.get(1) {
+                Some(Datum::Symbol(name)) => *name,
+                Some(Datum::List(signature)) => match signature.first() {
+                    Some(Datum::Symbol(name)) => *name,
+                    _ => return,
+                },
+                _ => return,
+            },
+            _ => return,
+        };
+        env.define_symbol(name, Value::Unassigned);
+    }
+}
+
+/// The special form `name` stands for, unless a variable shadows it.
+pub fn special_form(name: Symbol, env: &Env) -> Option<Operation> {
+    Operation::get(name).filter(|operation| operation.is_special_form() && !env.is_bound(name))
+}
+
+fn eval_atom(expression: &Datum, env: &Env) -> Result<Value> {
+    match expression {
+        Datum::Symbol(name) => match env.find_symbol(*name)? {
+            Value::Unassigned => Err(InterpreterError::RuntimeError(format!(
+                "{} is used before its definition",
+                name
+            ))),
+            value => Ok(value),
+        },
+        Datum::List(expressions) => Err(InterpreterError::SyntaxError(Datum::List(
+            expressions.clone(),
+        ))),
+        Datum::Str(value) => Ok(Value::string(value.as_ref())),
+        Datum::Float(value) => Ok(Value::Float(*value)),
+        Datum::Int(value) => Ok(Value::Int(*value)),
+        Datum::Bool(value) => Ok(Value::Bool(*value)),
+        Datum::Char(value) => Ok(Value::Char(*value)),
+        Datum::Bytevector(bytes) => Ok(Value::bytevector(bytes.to_vec())),
+        Datum::Nil => Ok(Value::Nil),
+        Datum::Keyword(name) => Ok(Value::Keyword(*name)),
+    }
+}
+
+impl Machine {
+    fn new(env: &Env) -> Self {
+        Machine {
+            stack: Vec::new(),
+            values: Vec::new(),
+            winders: None,
+            handlers: None,
+            global: env.global(),
+        }
+    }
+
+    fn run(&mut self, mut state: State) -> Result<Value> {
+        loop {
+            let step = match state {
+                State::Eval(expression, env) => self.eval(expression, env),
+                State::Return(value) => match self.stack.pop() {
+                    Some(frame) => self.resume(frame, value),
+                    None => return Ok(value),
+                },
+            };
+            state = match step {
+                Ok(state) => state,
+                Err(error) => self.handle_error(error)?,
+            };
+        }
+    }
+
+    /// Raises errors of the interpreter as error objects while there are
+    /// handlers for them, otherwise unwinds. Every failed attempt uninstalls
+    /// a handler or leaves a `dynamic-wind`, so this ends.
+    fn handle_error(&mut self, mut error: InterpreterError) -> Result<State> {
+        loop {
+            let handled = if self.handlers.is_some() {
+                self.raise(Value::ErrorObject(Rc::new(error.into())), false)
+            } else if self.winders.is_some() {
+                self.unwind(error)
+            } else {
+                return Err(error);
+            };
+            match handled {
+                Ok(state) => return Ok(state),
+                Err(next_error) => error = next_error,
+            }
+        }
+    }
+
+    /// Calls the innermost handler with `condition`, with the handler itself
+    /// uninstalled while it runs.
+    fn raise(&mut self, condition: Value, continuable: bool) -> Result<State> {
+        let handlers = match self.handlers.take() {
+            Some(handlers) => handlers,
+            None => return Err(InterpreterError::from_raised(condition)),
+        };
+        self.handlers = handlers.parent.clone();
+        match &handlers.handler {
+            Handler::Procedure(handler) => {
+                self.stack.push(match continuable {
+                    true => Frame::RestoreHandlers(Some(handlers.clone())),
+                    false => Frame::RaiseReturned(condition.clone()),
+                });
+                self.apply_to(handler.clone(), &[condition])
+            }
+            Handler::Guard(point) => {
+                let target_winders = match self.stack.get(self.guard_position(point)?) {
+                    Some(Frame::Guard { winders, .. }) => winders.clone(),
+                    _ => None,
+                };
+                let actions = wind_actions(&self.winders, &target_winders);
+                if actions.is_empty() {
+                    return self.enter_guard(point, condition);
                 }
-                _ => Err(InterpreterError::ValueError("wrong type for *".into())),
+                self.rewind(
+                    actions.into(),
+                    0,
+                    RewindTarget::Guard(point.clone(), condition),
+                )
+            }
+        }
+    }
+
+    fn guard_position(&self, guard_point: &Rc<EscapePoint>) -> Result<usize> {
+        self.stack
+            .iter()
+            .rposition(|frame| match frame {
+                Frame::Guard { point, .. } => Rc::ptr_eq(point, guard_point),
+                _ => false,
             })
-            .unwrap(),
-        Operation::Divide => expression_iter
-            .map(eval_w_env)
-            .reduce(|acc, elem| match (acc?, elem?) {
-                (SymbolicExpression::Float(acc_value), SymbolicExpression::Float(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value / elem_value))
-                }
-                (SymbolicExpression::Float(acc_value), SymbolicExpression::Int(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value / elem_value as f64))
+            .ok_or(InterpreterError::RuntimeError(
+                "raised to a guard that has already returned".into(),
+            ))
+    }
+
+    /// Drops everything above the frame of the `guard` and evaluates its
+    /// clauses with the condition bound.
+    fn enter_guard(&mut self, point: &Rc<EscapePoint>, condition: Value) -> Result<State> {
+        let position = self.guard_position(point)?;
+        let (clauses, mut env) = match self.stack.get(position) {
+            Some(Frame::Guard {
+                clauses,
+                env,
+                values_height,
+                winders,
+                handlers,
+                ..
+            }) => {
+                self.values.truncate(*values_height);
+                self.winders = winders.clone();
+                self.handlers = handlers.clone();
+                (clauses.clone(), env.clone())
+            }
+            _ => return Err(InterpreterError::RuntimeError("no guard to enter".into())),
+        };
+        self.stack.truncate(position);
+        match clauses.first() {
+            Some(Datum::Symbol(name)) => {
+                env.add_frame();
+                env.define_symbol(*name, condition.clone());
+                self.eval_guard_clause(clauses, 1, condition, env)
+            }
+            _ => Err(InterpreterError::ArgumentError(
+                "guard needs a variable for the condition".into(),
+            )),
+        }
+    }
+
+    /// Evaluates the `cond` like clauses of a `guard`, and raises the
+    /// condition again if none of them applies.
+    fn eval_guard_clause(
+        &mut self,
+        clauses: Rc<[Datum]>,
+        index: usize,
+        condition: Value,
+        env: Env,
+    ) -> Result<State> {
+        let clause = match clauses.get(index) {
+            None => return self.raise(condition, true),
+            Some(Datum::List(clause)) if !clause.is_empty() => clause.clone(),
+            Some(_) => {
+                return Err(InterpreterError::ArgumentError(
+                    "invalid clause for guard".into(),
+                ))
+            }
+        };
+        if clause[0] == Datum::Symbol(Symbol::intern("else")) {
+            return self.eval_sequence(clause, 1, env);
+        }
+        let test = clause[0].clone();
+        self.stack.push(Frame::GuardClause {
+            clauses,
+            index,
+            condition,
+            env: env.clone(),
+        });
+        Ok(State::Eval(test, env))
+    }
+
+    /// Leaves every active `dynamic-wind` before an error ends the
+    /// evaluation.
+    fn unwind(&mut self, error: InterpreterError) -> Result<State> {
+        if self.winders.is_none() {
+            return Err(error);
+        }
+        self.stack.clear();
+        self.values.clear();
+        let actions = wind_actions(&self.winders, &None);
+        self.rewind(actions.into(), 0, RewindTarget::Error(error))
+    }
+
+    fn rewind(
+        &mut self,
+        actions: Rc<[WindAction]>,
+        index: usize,
+        target: RewindTarget,
+    ) -> Result<State> {
+        // a `before` thunk has to run outside of its `dynamic-wind`, so the
+        // winder is only entered once the thunk has returned
+        if let Some(WindAction::Enter(winder)) = index.checked_sub(1).and_then(|i| actions.get(i)) {
+            self.winders = Some(winder.clone());
+        }
+        let (thunk, winders) = match actions.get(index) {
+            Some(WindAction::Exit(winder)) => (winder.after.clone(), winder.parent.clone()),
+            Some(WindAction::Enter(winder)) => (winder.before.clone(), winder.parent.clone()),
+            None => {
+                return match target {
+                    RewindTarget::Continuation(continuation, value) => {
+                        self.resume_continuation(&continuation)?;
+                        Ok(State::Return(value))
+                    }
+                    RewindTarget::Guard(point, condition) => self.enter_guard(&point, condition),
+                    RewindTarget::Error(error) => Err(error),
                 }
-                (SymbolicExpression::Int(acc_value), SymbolicExpression::Float(elem_value)) => {
-                    Ok(SymbolicExpression::Float(acc_value as f64 / elem_value))
+            }
+        };
+        self.winders = winders;
+        self.stack.push(Frame::Rewind {
+            actions,
+            index: index + 1,
+            target,
+        });
+        self.apply_to(thunk, &[])
+    }
+
+    /// Replaces the current stack with the one of `continuation`.
+    fn resume_continuation(&mut self, continuation: &Continuation) -> Result<()> {
+        match continuation {
+            Continuation::Full {
+                stack,
+                values,
+                winders,
+                handlers,
+            } => {
+                self.stack = stack.clone();
+                self.values = values.clone();
+                self.winders = winders.clone();
+                self.handlers = handlers.clone();
+            }
+            Continuation::Escape(escape_point) => {
+                let position = self.escape_position(escape_point)?;
+                if let Some(Frame::Escape {
+                    values_height,
+                    winders,
+                    handlers,
+                    ..
+                }) = self.stack.get(position)
+                {
+                    self.values.truncate(*values_height);
+                    self.winders = winders.clone();
+                    self.handlers = handlers.clone();
                 }
-                (SymbolicExpression::Int(acc_value), SymbolicExpression::Int(elem_value)) => Ok(
-                    SymbolicExpression::Float(acc_value as f64 / elem_value as f64),
-                ),
-                _ => Err(InterpreterError::ValueError("wrong types for /".into())),
+                self.stack.truncate(position);
+            }
+        }
+        Ok(())
+    }
+
+    fn escape_position(&self, escape_point: &Rc<EscapePoint>) -> Result<usize> {
+        self.stack
+            .iter()
+            .rposition(|frame| match frame {
+                Frame::Escape { point, .. } => Rc::ptr_eq(point, escape_point),
+                _ => false,
             })
-            .unwrap(),
-        Operation::Exp => match expression_iter.map(eval_w_env).next().unwrap()? {
-            SymbolicExpression::Float(value) => Ok(SymbolicExpression::Float(value.exp())),
-            SymbolicExpression::Int(value) => Ok(SymbolicExpression::Float((value as f64).exp())),
-            value => Err(InterpreterError::RuntimeError(
-                format!("exp on {}", value).to_string(),
-            )),
-        },
-        Operation::Pow => {
-            let mut evaluated_arguments = expression_iter.map(eval_w_env);
-            let value_first = evaluated_arguments.next().unwrap()?;
-            let value_second = evaluated_arguments.next().unwrap()?;
-            match (value_first, value_second) {
-                (SymbolicExpression::Float(first), SymbolicExpression::Float(second)) => {
-                    Ok(SymbolicExpression::Float(first.powf(second)))
+            .ok_or(InterpreterError::RuntimeError(
+                "escape continuation called after its extent ended".into(),
+            ))
+    }
+
+    fn eval(&mut self, expression: Datum, env: Env) -> Result<State> {
+        match expression {
+            Datum::List(expressions) => self.eval_expression(expressions, env),
+            atom => eval_atom(&atom, &env).map(State::Return),
+        }
+    }
+
+    fn eval_expression(&mut self, expressions: Rc<[Datum]>, env: Env) -> Result<State> {
+        let first_expression = match expressions.first() {
+            Some(first_expression) => first_expression.clone(),
+            None => return Err(InterpreterError::SyntaxError(Datum::List(expressions))),
+        };
+        if let Datum::Symbol(name) = first_expression {
+            if let Some(operation) = special_form(name, &env) {
+                return self.eval_special_form(operation, expressions, env);
+            }
+        }
+        self.eval_call(expressions, self.values.len(), env)
+    }
+
+    /// Evaluates the remaining operator and operands of a call, and applies
+    /// the procedure once all of them are on the value stack. Atoms are
+    /// evaluated in place, only nested expressions need a trip through
+    /// `run`.
+    fn eval_call(&mut self, expressions: Rc<[Datum]>, base: usize, env: Env) -> Result<State> {
+        loop {
+            match expressions.get(self.values.len() - base) {
+                None => return self.apply(base),
+                Some(Datum::List(_)) => break,
+                Some(atom) => {
+                    let value = eval_atom(atom, &env)?;
+                    self.values.push(value);
                 }
-                (SymbolicExpression::Float(first), SymbolicExpression::Int(second)) => {
-                    Ok(SymbolicExpression::Float(first.powi(second as i32)))
+            }
+        }
+        let expression = expressions[self.values.len() - base].clone();
+        self.stack.push(Frame::Call {
+            expressions,
+            base,
+            env: env.clone(),
+        });
+        Ok(State::Eval(expression, env))
+    }
+
+    fn eval_special_form(
+        &mut self,
+        operation: Operation,
+        expressions: Rc<[Datum]>,
+        mut env: Env,
+    ) -> Result<State> {
+        match operation {
+            Operation::Quote => expressions
+                .get(1)
+                .ok_or(missing_arguments())
+                .map(|expression| State::Return(Value::from(expression))),
+            Operation::If => {
+                let predicate = expressions.get(1).ok_or(missing_arguments())?.clone();
+                self.stack.push(Frame::If {
+                    consequent: expressions.get(2).cloned(),
+                    alternative: expressions.get(3).cloned(),
+                    env: env.clone(),
+                });
+                Ok(State::Eval(predicate, env))
+            }
+            Operation::Cond => self.eval_cond_clause(expressions, 1, env),
+            Operation::Begin => {
+                env.add_frame();
+                declare_definitions(&expressions[1..], &mut env);
+                self.eval_sequence(expressions, 1, env)
+            }
+            Operation::Module => self.eval_module(expressions, 1, env),
+            Operation::Define if matches!(expressions.get(1), Some(Datum::List(_))) => {
+                // example: (define (add a b) (+ a b))
+                let (name, parameters) = match expressions.get(1) {
+                    Some(Datum::List(signature)) => match signature.split_first() {
+                        Some((Datum::Symbol(name), parameters)) => (*name, parameters),
+                        _ => {
+                            return Err(InterpreterError::ArgumentError(format!(
+                                "invalid signature in define {}",
+                                expressions[1]
+                            )))
+                        }
+                    },
+                    _ => return Err(missing_arguments()),
+                };
+                let lambda = make_lambda(parameters, &expressions[2..], &env)?;
+                lambda.name.set(Some(name));
+                env.define_symbol(name, Value::Lambda(lambda));
+                Ok(State::Return(Value::Nil))
+            }
+            Operation::Define | Operation::Set => {
+                let name = match expressions.get(1) {
+                    Some(Datum::Symbol(name)) => *name,
+                    _ => {
+                        return Err(InterpreterError::ArgumentError(format!(
+                            "first argument to {} has to be symbol",
+                            expressions[0]
+                        )))
+                    }
+                };
+                let value_expression =
+                    expressions
+                        .get(2)
+                        .cloned()
+                        .ok_or(InterpreterError::ArgumentError(format!(
+                            "empty arguments for {}",
+                            expressions[0]
+                        )))?;
+                self.stack.push(match operation {
+                    Operation::Define => Frame::Define {
+                        name,
+                        env: env.clone(),
+                    },
+                    _ => Frame::Set {
+                        name,
+                        env: env.clone(),
+                    },
+                });
+                Ok(State::Eval(value_expression, env))
+            }
+            Operation::Lambda => match expressions.get(1) {
+                // example: (lambda (a b . rest) (cons (+ a b) rest))
+                Some(Datum::List(parameters)) => make_lambda(parameters, &expressions[2..], &env)
+                    .map(|lambda| State::Return(Value::Lambda(lambda))),
+                Some(rest @ Datum::Symbol(_)) => {
+                    make_lambda(&[dot(), rest.clone()], &expressions[2..], &env)
+                        .map(|lambda| State::Return(Value::Lambda(lambda)))
                 }
-                (SymbolicExpression::Int(first), SymbolicExpression::Float(second)) => {
-                    Ok(SymbolicExpression::Float((first as f64).powf(second)))
+                Some(other) => Err(InterpreterError::ArgumentError(format!(
+                    "invalid arg definition for lambda: {}",
+                    other,
+                ))),
+                None => Err(InterpreterError::ArgumentError(
+                    "missing arguments for lambda".into(),
+                )),
+            },
+            Operation::CaseLambda => {
+                // example: (case-lambda ((x) x) ((x y) (+ x y)))
+                let clauses = expressions[1..]
+                    .iter()
+                    .map(|clause| match clause {
+                        Datum::List(clause) => match clause.split_first() {
+                            Some((Datum::List(parameters), body)) => {
+                                make_lambda(parameters, body, &env)
+                            }
+                            Some((rest @ Datum::Symbol(_), body)) => {
+                                make_lambda(&[dot(), rest.clone()], body, &env)
+                            }
+                            _ => Err(InterpreterError::ArgumentError(format!(
+                                "invalid clause for case-lambda {}",
+                                Datum::List(clause.clone())
+                            ))),
+                        },
+                        _ => Err(InterpreterError::ArgumentError(format!(
+                            "invalid clause for case-lambda {}",
+                            clause
+                        ))),
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(State::Return(Value::CaseLambda(clauses)))
+            }
+            Operation::Let => {
+                // example: (let ((a 5) (b (+ 5 1))) (+ a b))
+                let bindings = match expressions.get(1) {
+                    Some(Datum::List(bindings)) => bindings.clone(),
+                    _ => {
+                        return Err(InterpreterError::ArgumentError(
+                            "invalid args for let".into(),
+                        ))
+                    }
+                };
+                if expressions.len() < 3 {
+                    return Err(missing_arguments());
                 }
-                (SymbolicExpression::Int(first), SymbolicExpression::Int(second)) => {
-                    if second < 0 {
-                        Ok(SymbolicExpression::Float(
-                            (first as f64).powi(second as i32),
+                env.add_frame();
+                self.eval_let_binding(bindings, 0, expressions, env)
+            }
+            Operation::Guard => {
+                // example: (guard (e ((error-object? e) (error-object-message e))) (error "oops"))
+                let clauses = match expressions.get(1) {
+                    Some(Datum::List(clauses)) => clauses.clone(),
+                    _ => {
+                        return Err(InterpreterError::ArgumentError(
+                            "invalid args for guard".into(),
                         ))
-                    } else {
-                        Ok(SymbolicExpression::Int(first.pow(second as u32)))
                     }
+                };
+                let point = Rc::new(EscapePoint);
+                self.stack.push(Frame::Guard {
+                    point: point.clone(),
+                    clauses,
+                    env: env.clone(),
+                    values_height: self.values.len(),
+                    winders: self.winders.clone(),
+                    handlers: self.handlers.clone(),
+                });
+                self.handlers = Some(Rc::new(Handlers {
+                    handler: Handler::Guard(point),
+                    parent: self.handlers.take(),
+                }));
+                env.add_frame();
+                declare_definitions(&expressions[2..], &mut env);
+                self.eval_sequence(expressions, 2, env)
+            }
+            Operation::MacroExpand | Operation::MacroExpand1 => {
+                let expression = expressions.get(1).ok_or(missing_arguments())?.clone();
+                self.stack.push(Frame::MacroExpand {
+                    once: operation == Operation::MacroExpand1,
+                    env: env.clone(),
+                });
+                Ok(State::Eval(expression, env))
+            }
+            _ => Err(InterpreterError::RuntimeError(format!(
+                "{:?} is not a special form",
+                operation
+            ))),
+        }
+    }
+
+    fn eval_cond_clause(&mut self, clauses: Rc<[Datum]>, index: usize, env: Env) -> Result<State> {
+        let predicate = match clauses.get(index) {
+            Some(Datum::List(values)) if values.len() >= 2 => {
+                if values[0] == Datum::Symbol(Symbol::intern("else")) {
+                    return self.eval_sequence(values.clone(), 1, env);
                 }
-                _ => Err(InterpreterError::ValueError("wrong types for pow".into())),
-            }
-        }
-        Operation::Begin => {
-            env.add_frame();
-            let result = expression_iter
-                .map(|el| eval(env, el))
-                .try_fold(SymbolicExpression::Nil, |_, res| res);
-            env.pop_frame();
-            result
-        }
-        Operation::Module => {
-            expression_iter.try_for_each(|el| eval_w_env(el).map(|_| ()))?;
-            Ok(SymbolicExpression::Nil)
-        }
-        Operation::Cons => {
-            let mut args = expression_iter.map(eval_w_env);
-            let head = Box::new(args.next().unwrap()?);
-            let tail = Box::new(args.next().unwrap()?);
-            Ok(SymbolicExpression::Cons { head, tail })
-        }
-        Operation::List => {
-            expression_iter
-                .map(eval_w_env)
-                .try_rfold(SymbolicExpression::Nil, |acc, elem| {
-                    Ok(SymbolicExpression::Cons {
-                        head: Box::new(elem?),
-                        tail: Box::new(acc),
-                    })
-                })
+                values[0].clone()
+            }
+            Some(_) => {
+                return Err(InterpreterError::ArgumentError(
+                    "invalid argument to cond".into(),
+                ))
+            }
+            None => {
+                return Err(InterpreterError::RuntimeError(
+                    "cond all predicate false".into(),
+                ))
+            }
+        };
+        self.stack.push(Frame::Cond {
+            clauses,
+            index,
+            env: env.clone(),
+        });
+        Ok(State::Eval(predicate, env))
+    }
+
+    fn eval_sequence(&mut self, expressions: Rc<[Datum]>, index: usize, env: Env) -> Result<State> {
+        match expressions.get(index).cloned() {
+            None => Ok(State::Return(Value::Nil)),
+            Some(expression) => {
+                if index + 1 < expressions.len() {
+                    self.stack.push(Frame::Sequence {
+                        expressions,
+                        index: index + 1,
+                        env: env.clone(),
+                    });
+                }
+                Ok(State::Eval(expression, env))
+            }
         }
-        Operation::Car => match expression_iter.map(eval_w_env).next().unwrap()? {
-            SymbolicExpression::Cons { head, .. } => Ok(*head),
-            _ => panic!("car on non cons type"),
-        },
-        Operation::Cdr => match expression_iter.map(eval_w_env).next().unwrap()? {
-            SymbolicExpression::Cons { tail, .. } => Ok(*tail),
-            _ => panic!("car on non cons type"),
-        },
-        Operation::Eq => Ok(eval_comparison_operation(
-            expression_iter
-                .map(eval_w_env)
-                .collect::<Result<Vec<SymbolicExpression>>>()?,
-            |left, right| left == right,
-        )),
-        Operation::Smaller => Ok(eval_comparison_operation(
-            expression_iter
-                .map(eval_w_env)
-                .collect::<Result<Vec<SymbolicExpression>>>()?,
-            |left, right| left < right,
-        )),
-        Operation::SmallerOrEqual => Ok(eval_comparison_operation(
-            expression_iter
-                .map(eval_w_env)
-                .collect::<Result<Vec<SymbolicExpression>>>()?,
-            |left, right| left <= right,
-        )),
-        Operation::Greater => Ok(eval_comparison_operation(
-            expression_iter
-                .map(eval_w_env)
-                .collect::<Result<Vec<SymbolicExpression>>>()?,
-            |left, right| left > right,
-        )),
-        Operation::GreaterOrEqual => Ok(eval_comparison_operation(
-            expression_iter
-                .map(eval_w_env)
-                .collect::<Result<Vec<SymbolicExpression>>>()?,
-            |left, right| left >= right,
-        )),
-        Operation::If => {
-            let predicate = eval_w_env(expression_iter.next().unwrap())?;
-            match predicate {
-                SymbolicExpression::Bool(true) => eval_w_env(expression_iter.next().unwrap()),
-                SymbolicExpression::Bool(false) => eval_w_env(expression_iter.nth(1).unwrap()),
-                _ => Err(InterpreterError::ValueError(
-                    "predicate must evaluate to boolean".into(),
-                )),
+    }
+
+    fn eval_module(&mut self, expressions: Rc<[Datum]>, index: usize, env: Env) -> Result<State> {
+        match expressions.get(index).cloned() {
+            None => Ok(State::Return(Value::Nil)),
+            Some(expression) => {
+                self.stack.push(Frame::Module {
+                    expressions,
+                    index: index + 1,
+                    env: env.clone(),
+                });
+                Ok(State::Eval(expression, env))
             }
         }
-        Operation::Cond => expression_iter
-            .find_map(|expression| match expression {
-                SymbolicExpression::Expression(values) => {
-                    let predicate = eval_w_env(&values[0]);
-                    match predicate {
-                        Ok(SymbolicExpression::Bool(true)) => Some(eval_w_env(&values[1])),
-                        Ok(SymbolicExpression::Bool(false)) => None,
-                        err if err.is_err() => Some(err),
-                        _ => Some(Err(InterpreterError::ValueError(
-                            "predicate must evaluate to boolean".into(),
-                        ))),
-                    }
+    }
+
+    fn eval_let_binding(
+        &mut self,
+        bindings: Rc<[Datum]>,
+        index: usize,
+        body: Rc<[Datum]>,
+        mut env: Env,
+    ) -> Result<State> {
+        let binding = match bindings.get(index) {
+            None => {
+                declare_definitions(&body[2..], &mut env);
+                return self.eval_sequence(body, 2, env);
+            }
+            Some(Datum::List(binding)) => binding,
+            Some(_) => {
+                return Err(InterpreterError::ArgumentError(
+                    "invalid args for let".into(),
+                ))
+            }
+        };
+        let (name, expression) = match binding.as_ref() {
+            [Datum::Symbol(name), expression, ..] => (*name, expression.clone()),
+            _ => {
+                return Err(InterpreterError::ArgumentError(
+                    "invalid args for let".into(),
+                ))
+            }
+        };
+        self.stack.push(Frame::Let {
+            bindings,
+            index,
+            name,
+            body,
+            env: env.clone(),
+        });
+        Ok(State::Eval(expression, env))
+    }
+
+    fn resume(&mut self, frame: Frame, value: Value) -> Result<State> {
+        match frame {
+            Frame::Call {
+                expressions,
+                base,
+                env,
+            } => {
+                self.values.push(value);
+                self.eval_call(expressions, base, env)
+            }
+            Frame::If {
+                consequent,
+                alternative,
+                env,
+            } => {
+                let branch = match value.is_true() {
+                    true => consequent,
+                    false => alternative,
+                };
+                match branch {
+                    Some(branch) => Ok(State::Eval(branch, env)),
+                    None => Ok(State::Return(Value::Nil)),
                 }
-                _ => Some(Err(InterpreterError::ArgumentError(
-                    "invalid argument to cond".into(),
-                ))),
-            })
-            .unwrap_or(Err(InterpreterError::RuntimeError(
-                "cond all predicate false".into(),
-            ))),
-        Operation::Quote => expression_iter
-            .next()
-            .ok_or(InterpreterError::ArgumentError("missing arguments".into()))
-            .cloned(),
-        Operation::Define => {
-            let name = match expression_iter.next() {
-                Some(SymbolicExpression::Symbol(value)) => value,
-                _ => {
-                    return Err(InterpreterError::ArgumentError(
-                        "first argument to define has to be symbol".into(),
-                    ))
+            }
+            Frame::Cond {
+                clauses,
+                index,
+                env,
+            } => match value.is_true() {
+                true => match clauses.get(index) {
+                    Some(Datum::List(values)) if values.len() >= 2 => {
+                        self.eval_sequence(values.clone(), 1, env)
+                    }
+                    _ => Err(InterpreterError::ArgumentError(
+                        "invalid argument to cond".into(),
+                    )),
+                },
+                false => self.eval_cond_clause(clauses, index + 1, env),
+            },
+            Frame::Sequence {
+                expressions,
+                index,
+                env,
+            } => self.eval_sequence(expressions, index, env),
+            Frame::Module {
+                expressions,
+                index,
+                env,
+            } => self.eval_module(expressions, index, env),
+            Frame::Define { name, mut env } => {
+                name_procedure(&value, name);
+                env.define_symbol(name, value);
+                Ok(State::Return(Value::Nil))
+            }
+            Frame::Set { name, mut env } => {
+                env.set_symbol(name, value)?;
+                Ok(State::Return(Value::Nil))
+            }
+            Frame::Let {
+                bindings,
+                index,
+                name,
+                body,
+                mut env,
+            } => {
+                name_procedure(&value, name);
+                env.define_symbol(name, value);
+                self.eval_let_binding(bindings, index + 1, body, env)
+            }
+            Frame::Default {
+                lambda,
+                defaults,
+                index,
+                mut env,
+            } => {
+                env.define_symbol(defaults[index].0, value);
+                self.eval_defaults(lambda, defaults, index + 1, env)
+            }
+            Frame::MacroExpand { once, env } => Ok(State::Return(Value::from(&macroexpand(
+                &env,
+                &value.to_datum()?,
+                once,
+            )?))),
+            Frame::Escape { .. } => Ok(State::Return(value)),
+            Frame::OutputString(port) => Ok(State::Return(Value::string(port.output_string()?))),
+            Frame::ClosePort(port) => {
+                port.close()?;
+                Ok(State::Return(value))
+            }
+            Frame::WindBefore {
+                before,
+                thunk,
+                after,
+            } => {
+                let winder = Rc::new(Winder {
+                    before,
+                    after,
+                    depth: winder_depth(&self.winders) + 1,
+                    parent: self.winders.take(),
+                });
+                self.winders = Some(winder.clone());
+                self.stack.push(Frame::WindBody(winder));
+                self.apply_to(thunk, &[])
+            }
+            Frame::WindBody(winder) => {
+                self.winders = winder.parent.clone();
+                self.stack.push(Frame::Deliver(value));
+                self.apply_to(winder.after.clone(), &[])
+            }
+            Frame::Deliver(value) => Ok(State::Return(value)),
+            Frame::Map {
+                procedure,
+                columns,
+                index,
+                results,
+                collect,
+            } => {
+                if let Some(result) = early_result(&collect, &columns, index, &value) {
+                    return Ok(State::Return(result));
                 }
-            };
-            let value_exp = expression_iter
-                .next()
-                .ok_or(InterpreterError::ArgumentError(
-                    "empty arguments for define".into(),
-                ))?;
-            let value = eval_w_env(value_exp)?;
-            env.define_symbol(name, value);
-            Ok(SymbolicExpression::Nil)
-        }
-        Operation::Set => {
-            let name = match expression_iter.next() {
-                Some(SymbolicExpression::Symbol(value)) => value,
-                _ => {
-                    return Err(InterpreterError::ArgumentError(
-                        "first argument to set! has to be symbol".into(),
-                    ))
+                let results = match collect {
+                    Collect::Nothing => results,
+                    _ => Value::Cons {
+                        head: Rc::new(value),
+                        tail: Rc::new(results),
+                    },
+                };
+                self.map_step(procedure, columns, index + 1, results, collect)
+            }
+            Frame::DeleteDuplicates {
+                procedure,
+                elements,
+                index,
+                kept,
+                remaining,
+            } => match value.is_true() {
+                true => {
+                    self.delete_duplicates_step(procedure, elements, index + 1, kept.clone(), kept)
                 }
-            };
-            let value_exp = expression_iter
-                .next()
-                .ok_or(InterpreterError::ArgumentError(
-                    "empty arguments for set!".into(),
-                ))?;
-            let value = eval_w_env(value_exp)?;
-            env.set_symbol(name, value)?;
-            Ok(SymbolicExpression::Nil)
-        }
-        Operation::Lambda => {
-            let parameters = match expression_iter.next().unwrap() {
-                SymbolicExpression::Expression(values) => values
+                false => self.delete_duplicates_step(procedure, elements, index, kept, remaining),
+            },
+            Frame::Sort(mut sort) => {
+                sort.compared(value.is_true());
+                self.sort_step(sort)
+            }
+            Frame::Fold {
+                procedure,
+                columns,
+                index,
+                accumulator_first,
+            } => self.fold_step(procedure, columns, index + 1, value, accumulator_first),
+            Frame::Guard { handlers, .. } => {
+                self.handlers = handlers;
+                Ok(State::Return(value))
+            }
+            Frame::GuardClause {
+                clauses,
+                index,
+                condition,
+                env,
+            } => match value.is_true() {
+                true => match clauses.get(index) {
+                    Some(Datum::List(clause)) if clause.len() > 1 => {
+                        self.eval_sequence(clause.clone(), 1, env)
+                    }
+                    _ => Ok(State::Return(value)),
+                },
+                false => self.eval_guard_clause(clauses, index + 1, condition, env),
+            },
+            Frame::RestoreHandlers(handlers) => {
+                self.handlers = handlers;
+                Ok(State::Return(value))
+            }
+            Frame::RaiseReturned(condition) => self.raise(
+                Value::ErrorObject(Rc::new(ErrorObject {
+                    message: "handler returned from non-continuable raise".into(),
+                    irritants: Value::Cons {
+                        head: Rc::new(condition),
+                        tail: Rc::new(Value::Nil),
+                    },
+                    cause: None,
+                })),
+                false,
+            ),
+            Frame::Rewind {
+                actions,
+                index,
+                target,
+            } => self.rewind(actions, index, target),
+        }
+    }
+
+    /// Applies the procedure at `base` of the value stack to the arguments
+    /// above it, and pops all of them.
+    fn apply(&mut self, base: usize) -> Result<State> {
+        let procedure = self.values[base].clone();
+        let count = self.values.len() - base - 1;
+        match &procedure {
+            Value::Operation(operation) => check_arity(operation.name(), operation.arity(), count)?,
+            Value::Continuation(_) => check_arity("continuation", (0, Some(1)), count)?,
+            _ => {}
+        }
+        let state = match &procedure {
+            Value::Lambda(lambda) => self.apply_lambda(lambda.clone(), base)?,
+            Value::CaseLambda(clauses) => {
+                match clauses
                     .iter()
-                    .map(|each| match each {
-                        SymbolicExpression::Symbol(name) => Ok(name.to_owned()),
-                        _ => Err(InterpreterError::ArgumentError(format!(
-                            "non symbol arg in lambda {}",
-                            each
-                        ))),
+                    .find(|lambda| lambda.parameters.accepts(count))
+                {
+                    Some(lambda) => self.apply_lambda(lambda.clone(), base)?,
+                    None => {
+                        return Err(InterpreterError::ArgumentError(format!(
+                            "no clause of case-lambda takes {} arguments",
+                            count
+                        )))
+                    }
+                }
+            }
+            Value::Continuation(continuation) => {
+                let value = self.values.get(base + 1).cloned().unwrap_or(Value::Nil);
+                self.values.truncate(base);
+                let target_winders = match continuation.as_ref() {
+                    Continuation::Full { winders, .. } => winders.clone(),
+                    Continuation::Escape(escape_point) => {
+                        match self.stack.get(self.escape_position(escape_point)?) {
+                            Some(Frame::Escape { winders, .. }) => winders.clone(),
+                            _ => None,
+                        }
+                    }
+                };
+                let actions = wind_actions(&self.winders, &target_winders);
+                if actions.is_empty() {
+                    self.resume_continuation(continuation)?;
+                    return Ok(State::Return(value));
+                }
+                return self.rewind(
+                    actions.into(),
+                    0,
+                    RewindTarget::Continuation(continuation.clone(), value),
+                );
+            }
+            Value::Operation(Operation::CallCC) => {
+                let receiver = self
+                    .values
+                    .get(base + 1)
+                    .cloned()
+                    .ok_or(missing_arguments())?;
+                self.values.truncate(base);
+                let continuation = Continuation::Full {
+                    stack: self.stack.clone(),
+                    values: self.values.clone(),
+                    winders: self.winders.clone(),
+                    handlers: self.handlers.clone(),
+                };
+                return self.apply_to(receiver, &[Value::Continuation(Rc::new(continuation))]);
+            }
+            Value::Operation(Operation::CallEC) => {
+                let receiver = self
+                    .values
+                    .get(base + 1)
+                    .cloned()
+                    .ok_or(missing_arguments())?;
+                self.values.truncate(base);
+                let point = Rc::new(EscapePoint);
+                self.stack.push(Frame::Escape {
+                    point: point.clone(),
+                    values_height: base,
+                    winders: self.winders.clone(),
+                    handlers: self.handlers.clone(),
+                });
+                let continuation = Continuation::Escape(point);
+                return self.apply_to(receiver, &[Value::Continuation(Rc::new(continuation))]);
+            }
+            Value::Operation(Operation::DynamicWind) => {
+                let (before, thunk, after) = match &self.values[base + 1..] {
+                    [before, thunk, after] => (before.clone(), thunk.clone(), after.clone()),
+                    _ => {
+                        return Err(InterpreterError::ArgumentError(
+                            "dynamic-wind takes a before, a body and an after thunk".into(),
+                        ))
+                    }
+                };
+                self.values.truncate(base);
+                self.stack.push(Frame::WindBefore {
+                    before: before.clone(),
+                    thunk,
+                    after,
+                });
+                return self.apply_to(before, &[]);
+            }
+            Value::Operation(
+                operation @ (Operation::WithOutputToString
+                | Operation::WithOutputToPort
+                | Operation::WithOutputToFile),
+            ) => {
+                let thunk = self.values[base + count].clone();
+                let port = match operation {
+                    Operation::WithOutputToString => {
+                        let port = Rc::new(Port::StringOutput(RefCell::default()));
+                        self.stack.push(Frame::OutputString(port.clone()));
+                        port
+                    }
+                    Operation::WithOutputToFile => {
+                        let port =
+                            Rc::new(Port::open_output_file(&file_name(&self.values[base + 1])?)?);
+                        self.stack.push(Frame::ClosePort(port.clone()));
+                        port
+                    }
+                    _ => match &self.values[base + 1] {
+                        Value::Port(port) => port.clone(),
+                        value => return Err(not_a("port", value)),
+                    },
+                };
+                self.values.truncate(base);
+                let previous = Rc::new(RefCell::new(None));
+                let before = {
+                    let (port, previous) = (port.clone(), previous.clone());
+                    Value::native(operation.name(), move |_| {
+                        *previous.borrow_mut() = Some(set_current_output(port.clone()));
+                        Ok(Value::Nil)
                     })
-                    .collect(),
-                other => Err(InterpreterError::ArgumentError(format!(
-                    "invalid arg definition for lambda: {}",
-                    other,
-                ))),
-            }?;
+                };
+                let after = Value::native(operation.name(), move |_| {
+                    set_current_output(previous.take().unwrap_or_else(current_output));
+                    Ok(Value::Nil)
+                });
+                self.stack.push(Frame::WindBefore {
+                    before: before.clone(),
+                    thunk,
+                    after,
+                });
+                return self.apply_to(before, &[]);
+            }
+            Value::Operation(
+                operation @ (Operation::CallWithInputFile | Operation::CallWithOutputFile),
+            ) => {
+                let name = file_name(&self.values[base + 1])?;
+                let procedure = self.values[base + 2].clone();
+                self.values.truncate(base);
+                let port = Rc::new(match operation {
+                    Operation::CallWithInputFile => Port::open_input_file(&name)?,
+                    _ => Port::open_output_file(&name)?,
+                });
+                self.stack.push(Frame::ClosePort(port.clone()));
+                return self.apply_to(procedure, &[Value::Port(port)]);
+            }
+            Value::Operation(Operation::CallWithOutputString) => {
+                let procedure = self.values[base + 1].clone();
+                self.values.truncate(base);
+                let port = Rc::new(Port::StringOutput(RefCell::default()));
+                self.stack.push(Frame::OutputString(port.clone()));
+                return self.apply_to(procedure, &[Value::Port(port)]);
+            }
+            Value::Operation(Operation::WithExceptionHandler) => {
+                let (handler, thunk) = match &self.values[base + 1..] {
+                    [handler, thunk] => (handler.clone(), thunk.clone()),
+                    _ => {
+                        return Err(InterpreterError::ArgumentError(
+                            "with-exception-handler takes a handler and a thunk".into(),
+                        ))
+                    }
+                };
+                self.values.truncate(base);
+                self.stack
+                    .push(Frame::RestoreHandlers(self.handlers.clone()));
+                self.handlers = Some(Rc::new(Handlers {
+                    handler: Handler::Procedure(handler),
+                    parent: self.handlers.take(),
+                }));
+                return self.apply_to(thunk, &[]);
+            }
+            &Value::Operation(
+                operation @ (Operation::Raise | Operation::RaiseContinuable | Operation::Error),
+            ) => {
+                let condition = match operation {
+                    Operation::Error => make_error_object(&self.values[base + 1..])?,
+                    _ => self
+                        .values
+                        .get(base + 1)
+                        .cloned()
+                        .ok_or(missing_arguments())?,
+                };
+                self.values.truncate(base);
+                return self.raise(condition, operation == Operation::RaiseContinuable);
+            }
+            Value::Operation(Operation::Eval) => {
+                let env = match self.values.get(base + 2) {
+                    Some(Value::Environment(env)) => env.clone(),
+                    Some(value) => return Err(not_a("environment", value)),
+                    None => self.global.clone(),
+                };
+                let expression = expand(&env, &self.values[base + 1].to_datum()?)?;
+                self.values.truncate(base);
+                return Ok(State::Eval(expression, env));
+            }
+            Value::Operation(Operation::InteractionEnvironment) => {
+                self.values.truncate(base);
+                return Ok(State::Return(Value::Environment(self.global.clone())));
+            }
+            Value::Operation(Operation::Apply) => {
+                let mut arguments = self.values.split_off(base + 1);
+                self.values.truncate(base);
+                let procedure = arguments.remove(0);
+                let spread = arguments.pop().ok_or(missing_arguments())?.to_vec()?;
+                arguments.extend(spread);
+                return self.apply_to(procedure, &arguments);
+            }
+            Value::Operation(
+                operation @ (Operation::Map
+                | Operation::ForEach
+                | Operation::VectorMap
+                | Operation::StringMap),
+            ) => {
+                let procedure = self.values[base + 1].clone();
+                let (items, collect): (Items, _) = match operation {
+                    Operation::Map => (Value::to_vec, Collect::List),
+                    Operation::ForEach => (Value::to_vec, Collect::Nothing),
+                    Operation::VectorMap => (vector_items, Collect::Vector),
+                    _ => (string_chars, Collect::String),
+                };
+                let columns = columns(&self.values[base + 2..], items)?;
+                self.values.truncate(base);
+                return self.map_step(procedure, columns, 0, Value::Nil, collect);
+            }
+            Value::Operation(
+                operation @ (Operation::Filter
+                | Operation::Remove
+                | Operation::Partition
+                | Operation::Find
+                | Operation::FindTail
+                | Operation::Any
+                | Operation::Every
+                | Operation::Count
+                | Operation::AppendMap
+                | Operation::FilterMap),
+            ) => {
+                let procedure = self.values[base + 1].clone();
+                let collect = match operation {
+                    Operation::Filter => Collect::Filter(true),
+                    Operation::Remove => Collect::Filter(false),
+                    Operation::Partition => Collect::Partition,
+                    Operation::Find => Collect::Find,
+                    Operation::FindTail => Collect::FindTail,
+                    Operation::Any => Collect::Any,
+                    Operation::Every => Collect::Every,
+                    Operation::Count => Collect::Count,
+                    Operation::AppendMap => Collect::AppendMap,
+                    _ => Collect::FilterMap,
+                };
+                let columns = columns(&self.values[base + 2..], Value::to_vec)?;
+                self.values.truncate(base);
+                return self.map_step(procedure, columns, 0, Value::Nil, collect);
+            }
+            Value::Operation(
+                operation @ (Operation::Delete | Operation::Member | Operation::Assoc),
+            ) if count == 3 => {
+                let (item, list, procedure) = (
+                    self.values[base + 1].clone(),
+                    self.values[base + 2].to_vec()?,
+                    self.values[base + 3].clone(),
+                );
+                self.values.truncate(base);
+                let (compared, collect) = match operation {
+                    Operation::Delete => (list, Collect::Filter(false)),
+                    Operation::Member => (list, Collect::FindTail),
+                    _ => {
+                        let keys = list
+                            .iter()
+                            .map(|entry| match entry {
+                                Value::Cons { head, .. } => Ok(head.as_ref().clone()),
+                                entry => Err(not_a("pair", entry)),
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        (keys, Collect::Assoc(list.into()))
+                    }
+                };
+                let items = vec![item; compared.len()];
+                let columns: Columns = Rc::new([items.into(), compared.into()]);
+                return self.map_step(procedure, columns, 0, Value::Nil, collect);
+            }
+            Value::Operation(Operation::DeleteDuplicates) if count == 2 => {
+                let elements = self.values[base + 1].to_vec()?;
+                let procedure = self.values[base + 2].clone();
+                self.values.truncate(base);
+                return self.delete_duplicates_step(
+                    procedure,
+                    elements.into(),
+                    0,
+                    Value::Nil,
+                    Value::Nil,
+                );
+            }
+            Value::Operation(Operation::StringIndex)
+                if !matches!(self.values[base + 2], Value::Char(_)) =>
+            {
+                let columns = columns(&self.values[base + 1..base + 2], string_chars)?;
+                let procedure = self.values[base + 2].clone();
+                self.values.truncate(base);
+                return self.map_step(procedure, columns, 0, Value::Nil, Collect::Index);
+            }
+            Value::Operation(Operation::ListSort) => {
+                let procedure = self.values[base + 1].clone();
+                let elements = self.values[base + 2].to_vec()?;
+                self.values.truncate(base);
+                return self.sort_step(Sort::new(procedure, elements.into(), SortTarget::List));
+            }
+            Value::Operation(
+                operation @ (Operation::VectorSort | Operation::VectorSortInPlace),
+            ) => {
+                let (procedure, vector) = match operation {
+                    Operation::VectorSort => (&self.values[base + 1], &self.values[base + 2]),
+                    _ => (&self.values[base + 2], &self.values[base + 1]),
+                };
+                let procedure = procedure.clone();
+                let Value::Vector(vector) = vector else {
+                    return Err(not_a("vector", vector));
+                };
+                let vector = vector.clone();
+                let (start, end) = slice_bounds(&self.values[base + 3..], vector.borrow().len())?;
+                let elements: Rc<[Value]> = vector.borrow()[start..end].into();
+                self.values.truncate(base);
+                let target = match operation {
+                    Operation::VectorSort => SortTarget::Vector,
+                    _ => SortTarget::InPlace(vector, start),
+                };
+                return self.sort_step(Sort::new(procedure, elements, target));
+            }
+            Value::Operation(Operation::ListMerge) => {
+                let procedure = self.values[base + 1].clone();
+                let first = self.values[base + 2].to_vec()?;
+                let second = self.values[base + 3].to_vec()?;
+                self.values.truncate(base);
+                return self.sort_step(Sort::merge(procedure, first, second));
+            }
+            Value::Operation(Operation::IsSorted) => {
+                let elements = match &self.values[base + 1] {
+                    Value::Vector(vector) => vector.borrow().clone(),
+                    list => list.to_vec()?,
+                };
+                let procedure = self.values[base + 2].clone();
+                self.values.truncate(base);
+                let later = elements.iter().skip(1).cloned().collect();
+                let earlier = elements.iter().cloned().collect();
+                let columns: Columns = Rc::new([later, earlier]);
+                return self.map_step(procedure, columns, 0, Value::Nil, Collect::NotAny);
+            }
+            &Value::Operation(operation @ (Operation::FoldLeft | Operation::FoldRight)) => {
+                let procedure = self.values[base + 1].clone();
+                let initial = self.values[base + 2].clone();
+                let mut columns = columns(&self.values[base + 3..], Value::to_vec)?;
+                if operation == Operation::FoldRight {
+                    columns = columns
+                        .iter()
+                        .map(|column| column.iter().rev().cloned().collect())
+                        .collect();
+                }
+                self.values.truncate(base);
+                let accumulator_first = operation == Operation::FoldLeft;
+                return self.fold_step(procedure, columns, 0, initial, accumulator_first);
+            }
+            // SRFI-1's `(reduce procedure identity list)`. The `(reduce
+            // procedure list)` std.scm had before is `reduce-recursive` now,
+            // which folds from the right and fails on the empty list.
+            Value::Operation(Operation::Reduce) => {
+                let procedure = self.values[base + 1].clone();
+                let identity = self.values[base + 2].clone();
+                let list = self.values[base + 3].to_vec()?;
+                self.values.truncate(base);
+                return match list.split_first() {
+                    Some((first, rest)) => {
+                        let columns: Columns = Rc::new([rest.into()]);
+                        self.fold_step(procedure, columns, 0, first.clone(), false)
+                    }
+                    None => Ok(State::Return(identity)),
+                };
+            }
+            &Value::Operation(operation) if !operation.is_special_form() => {
+                State::Return(apply_primitive(operation, &self.values[base + 1..])?)
+            }
+            Value::Native(native) => State::Return((native.function)(&self.values[base + 1..])?),
+            other => {
+                return Err(InterpreterError::RuntimeError(format!(
+                    "{} is not a procedure",
+                    other
+                )))
+            }
+        };
+        self.values.truncate(base);
+        Ok(state)
+    }
 
-            let body: Box<SymbolicExpression> = Box::new(expression_iter.next().unwrap().clone());
-            let lambda_env = env.get_lambda_env();
-            Ok(SymbolicExpression::Lambda {
-                parameters,
-                env: lambda_env,
-                body,
-            })
+    /// Binds the arguments on the value stack from `base` in a new frame.
+    /// Missing optional and key parameters get their defaults computed
+    /// before the body runs.
+    fn apply_lambda(&mut self, lambda: Rc<Lambda>, base: usize) -> Result<State> {
+        let name = lambda.name.get().map(|name| name.strip().name());
+        check_arity(
+            name.as_deref().unwrap_or("lambda"),
+            lambda.parameters.arity(),
+            self.values.len() - base - 1,
+        )?;
+        let mut env = lambda.env.clone();
+        env.add_frame();
+        let parameters = &lambda.parameters;
+        let mut arguments = &self.values[base + 1..];
+        for parameter in &parameters.required {
+            if let Some((argument, rest)) = arguments.split_first() {
+                env.define_symbol(*parameter, argument.clone());
+                arguments = rest;
+            }
         }
-        Operation::Let => {
-            // example: (let ((a 5) (b (+ 5 1))) (+ a b))
-            env.add_frame();
-            if let Some(SymbolicExpression::Expression(expression)) = expression_iter.next() {
-                expression.iter().try_for_each(|each| {
-                    match each {
-                        SymbolicExpression::Expression(sub_expression) => {
-                            let mut sub_iter = sub_expression.iter();
-                            if let Some(SymbolicExpression::Symbol(name)) = sub_iter.next() {
-                                let exp = sub_iter.next().unwrap();
-                                let value = eval(env, exp)?;
-                                env.define_symbol(name, value);
-                            } else {
-                                return Err(InterpreterError::ArgumentError(
-                                    "invalid args for let".to_string(),
-                                ));
-                            }
-                        }
-                        _ => {
-                            return Err(InterpreterError::ArgumentError(
-                                "invalid args for let".to_string(),
-                            ))
-                        }
-                    };
-                    Result::Ok(())
-                })?
-            } else {
-                panic!("invalid args for let")
+        let mut missing = Vec::new();
+        for (parameter, default) in &parameters.optional {
+            match arguments.split_first() {
+                Some((argument, rest)) => {
+                    env.define_symbol(*parameter, argument.clone());
+                    arguments = rest;
+                }
+                None => missing.push((*parameter, default.clone())),
             }
-            let result = eval(env, expression_iter.next().unwrap());
-            env.pop_frame();
-            result
         }
+        if let Some(rest) = parameters.rest {
+            env.define_symbol(rest, apply_primitive(Operation::List, arguments)?);
+        }
+        if !parameters.keys.is_empty() {
+            bind_keywords(parameters, arguments, &mut env, &mut missing)?;
+        }
+        if missing.is_empty() {
+            declare_definitions(&lambda.body, &mut env);
+            return self.eval_sequence(lambda.body.clone(), 0, env);
+        }
+        self.eval_defaults(lambda, missing.into(), 0, env)
     }
-}
 
-fn eval_lambda<'a>(
-    env: &mut Env,
-    lambda_env: &mut Env,
-    parameters: &[String],
-    body: &SymbolicExpression,
-    expression_iter: &mut impl DoubleEndedIterator<Item = &'a SymbolicExpression>,
-) -> Result<SymbolicExpression> {
-    lambda_env.add_frame();
-    parameters
-        .iter()
-        .zip(expression_iter)
-        .try_for_each(|(param, expression)| {
-            eval(env, expression).map(|value| lambda_env.define_symbol(param, value))
-        })?;
-
-    let result = eval(lambda_env, body);
-    lambda_env.pop_frame();
-    result
-}
-
-fn eval_expression(env: &mut Env, expression: &[SymbolicExpression]) -> Result<SymbolicExpression> {
-    let mut expression_iter = expression.iter();
-
-    let first_expression = eval(env, expression_iter.next().unwrap())?;
-
-    match first_expression {
-        SymbolicExpression::Operation(operation) => {
-            eval_operation(env, operation, &mut expression_iter)
-        }
-        SymbolicExpression::Lambda {
-            parameters,
-            env: mut lambda_env,
-            body,
-        } => eval_lambda(
-            env,
-            &mut lambda_env,
-            &parameters,
-            &body,
-            &mut expression_iter,
-        ),
-        _ => Err(InterpreterError::SyntaxError(first_expression)),
+    fn eval_defaults(
+        &mut self,
+        lambda: Rc<Lambda>,
+        defaults: Rc<[(Symbol, Datum)]>,
+        index: usize,
+        mut env: Env,
+    ) -> Result<State> {
+        match defaults.get(index) {
+            Some((_, default)) => {
+                let default = default.clone();
+                self.stack.push(Frame::Default {
+                    lambda,
+                    defaults,
+                    index,
+                    env: env.clone(),
+                });
+                Ok(State::Eval(default, env))
+            }
+            None => {
+                declare_definitions(&lambda.body, &mut env);
+                self.eval_sequence(lambda.body.clone(), 0, env)
+            }
+        }
     }
-}
 
-pub fn eval(env: &mut Env, expression: &SymbolicExpression) -> Result<SymbolicExpression> {
-    match expression {
-        SymbolicExpression::Symbol(name) => env.find_symbol(name),
-        SymbolicExpression::Expression(expression) => eval_expression(env, expression),
-        value => Ok(value.clone()),
+    /// Calls `procedure` on the elements at `index` of `columns`, with the
+    /// accumulator before or after them for folds.
+    fn apply_to_row(
+        &mut self,
+        procedure: Value,
+        columns: &Columns,
+        index: usize,
+        accumulator: Option<(Value, bool)>,
+    ) -> Result<State> {
+        let base = self.values.len();
+        self.values.push(procedure);
+        if let Some((accumulator, true)) = &accumulator {
+            self.values.push(accumulator.clone());
+        }
+        self.values
+            .extend(columns.iter().map(|column| column[index].clone()));
+        if let Some((accumulator, false)) = accumulator {
+            self.values.push(accumulator);
+        }
+        self.apply(base)
+    }
+
+    fn map_step(
+        &mut self,
+        procedure: Value,
+        columns: Columns,
+        index: usize,
+        results: Value,
+        collect: Collect,
+    ) -> Result<State> {
+        if columns.first().is_none_or(|column| index >= column.len()) {
+            return collect_results(results, collect, &columns).map(State::Return);
+        }
+        self.stack.push(Frame::Map {
+            procedure: procedure.clone(),
+            columns: columns.clone(),
+            index,
+            results,
+            collect,
+        });
+        self.apply_to_row(procedure, &columns, index, None)
+    }
+
+    fn delete_duplicates_step(
+        &mut self,
+        procedure: Value,
+        elements: Rc<[Value]>,
+        index: usize,
+        kept: Value,
+        remaining: Value,
+    ) -> Result<State> {
+        let Some(element) = elements.get(index).cloned() else {
+            let mut kept = kept.to_vec()?;
+            kept.reverse();
+            return Ok(State::Return(Value::list(kept.into_iter())));
+        };
+        match &remaining {
+            Value::Cons { head, tail } => {
+                self.stack.push(Frame::DeleteDuplicates {
+                    procedure: procedure.clone(),
+                    elements,
+                    index,
+                    kept,
+                    remaining: tail.as_ref().clone(),
+                });
+                self.apply_to(procedure, &[head.as_ref().clone(), element])
+            }
+            _ => {
+                let kept = Value::Cons {
+                    head: Rc::new(element),
+                    tail: Rc::new(kept),
+                };
+                self.delete_duplicates_step(procedure, elements, index + 1, kept.clone(), kept)
+            }
+        }
     }
+
+    fn sort_step(&mut self, mut sort: Sort) -> Result<State> {
+        match sort.advance()? {
+            Some((right, left)) => {
+                let procedure = sort.procedure.clone();
+                self.stack.push(Frame::Sort(sort));
+                self.apply_to(procedure, &[right, left])
+            }
+            None => Ok(State::Return(sort.finish())),
+        }
+    }
+
+    fn fold_step(
+        &mut self,
+        procedure: Value,
+        columns: Columns,
+        index: usize,
+        accumulator: Value,
+        accumulator_first: bool,
+    ) -> Result<State> {
+        if columns.first().is_none_or(|column| index >= column.len()) {
+            return Ok(State::Return(accumulator));
+        }
+        self.stack.push(Frame::Fold {
+            procedure: procedure.clone(),
+            columns: columns.clone(),
+            index,
+            accumulator_first,
+        });
+        self.apply_to_row(
+            procedure,
+            &columns,
+            index,
+            Some((accumulator, accumulator_first)),
+        )
+    }
+
+    fn apply_to(&mut self, procedure: Value, arguments: &[Value]) -> Result<State> {
+        let base = self.values.len();
+        self.values.push(procedure);
+        self.values.extend_from_slice(arguments);
+        self.apply(base)
+    }
+}
+
+/// Calls `procedure` outside of any evaluation, like macro transformers
+/// are called during expansion.
+pub fn apply_procedure(env: &Env, procedure: Value, arguments: &[Value]) -> Result<Value> {
+    let mut machine = Machine::new(env);
+    let state = machine.apply_to(procedure, arguments)?;
+    machine.run(state)
+}
+
+/// Expands the macros in `expression` and evaluates the result.
+pub fn eval(env: &mut Env, expression: &Datum) -> Result<Value> {
+    let expression = expand(env, expression)?;
+    Machine::new(env).run(State::Eval(expression, env.clone()))
 }
@@ -0,0 +1,962 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::env::Env;
+use crate::error::{InterpreterError, Result};
+use crate::eval::{apply_procedure, eval, special_form};
+use crate::parse::{Datum, MAX_DEPTH};
+use crate::symbol::{Symbol, SymbolMap};
+use crate::value::{Operation, Value};
+
+#[derive(Debug)]
+pub enum Macro {
+    SyntaxRules(SyntaxRules),
+    /// An `er-macro-transformer`, a procedure from the form, a `rename` and
+    /// a `compare` procedure to the expansion.
+    ExplicitRenaming {
+        procedure: Value,
+        scope: Scope,
+    },
+}
+
+/// A `syntax-rules` transformer. Identifiers its templates insert refer to
+/// the bindings of the scope the macro was defined in.
+#[derive(Debug)]
+pub struct SyntaxRules {
+    literals: Vec<Symbol>,
+    ellipsis: Symbol,
+    rules: Vec<(Rc<[Datum]>, Datum)>,
+    scope: Scope,
+}
+
+/// Compile time counterpart of the frames of an `Env`: what the identifiers
+/// bound by the forms around an expression stand for. The top level scope
+/// is empty, its identifiers are looked up in the environment.
+#[derive(Debug, Clone, Default)]
+pub struct Scope(Option<Rc<ScopeFrame>>);
+
+#[derive(Debug)]
+struct ScopeFrame {
+    bindings: RefCell<SymbolMap<Binding>>,
+    outer: Scope,
+}
+
+#[derive(Debug, Clone)]
+enum Binding {
+    /// A local variable, renamed to a fresh symbol.
+    Variable(Symbol),
+    Macro(Rc<Macro>),
+}
+
+/// What an identifier refers to where it is used.
+#[derive(Debug, Clone)]
+enum Meaning {
+    Variable(Symbol),
+    Macro(Rc<Macro>),
+    SpecialForm(Operation),
+}
+
+impl PartialEq for Meaning {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Variable(left), Self::Variable(right)) => left == right,
+            (Self::Macro(left), Self::Macro(right)) => Rc::ptr_eq(left, right),
+            (Self::SpecialForm(left), Self::SpecialForm(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+/// Forms bound to the pattern variables of a matching rule.
+#[derive(Debug, Clone)]
+enum Matched {
+    One(Datum),
+    /// Bound under an ellipsis, one entry per repetition.
+    Many(Vec<Matched>),
+}
+
+type Matches = SymbolMap<Matched>;
+
+impl Scope {
+    fn extend(&self) -> Scope {
+        Scope(Some(Rc::new(ScopeFrame {
+            bindings: RefCell::default(),
+            outer: self.clone(),
+        })))
+    }
+
+    fn is_top_level(&self) -> bool {
+        self.0.is_none()
+    }
+
+    fn lookup(&self, symbol: Symbol) -> Option<Binding> {
+        let mut scope = self;
+        while let Some(frame) = &scope.0 {
+            if let Some(binding) = frame.bindings.borrow().get(&symbol) {
+                return Some(binding.clone());
+            }
+            scope = &frame.outer;
+        }
+        None
+    }
+
+    /// Binds `symbol` in the innermost frame, top level bindings go to the
+    /// environment instead.
+    fn bind(&self, symbol: Symbol, binding: Binding) {
+        if let Some(frame) = &self.0 {
+            frame.bindings.borrow_mut().insert(symbol, binding);
+        }
+    }
+
+    fn local(&self, symbol: Symbol) -> Option<Binding> {
+        self.0
+            .as_ref()
+            .and_then(|frame| frame.bindings.borrow().get(&symbol).cloned())
+    }
+}
+
+/// Scope of the macro each alias was inserted by. Shared with the `rename`
+/// and `compare` procedures of explicit renaming macros.
+type Aliases = Rc<RefCell<SymbolMap<Scope>>>;
+
+struct Expander {
+    env: Env,
+    aliases: Aliases,
+    /// How many local variables of each name were renamed so far.
+    renames: RefCell<SymbolMap<usize>>,
+    /// Aliases that outlive the expansion in the templates of macros it
+    /// defined at the top level, the others are freed when it is done.
+    kept: Vec<Symbol>,
+    /// How many forms are being expanded inside each other, which deeply
+    /// nested expansions or macros that keep expanding into themselves
+    /// could take past what the stack holds.
+    depth: usize,
+}
+
+fn invalid_syntax(form: &[Datum]) -> InterpreterError {
+    InterpreterError::SyntaxError(Datum::List(form.into()))
+}
+
+/// Replaces aliases by the symbols they were created for, so quoted data
+/// looks like it was written.
+fn strip_datum(datum: &Datum) -> Datum {
+    match datum {
+        Datum::Symbol(symbol) => Datum::Symbol(symbol.strip()),
+        Datum::List(items) => Datum::List(items.iter().map(strip_datum).collect()),
+        other => other.clone(),
+    }
+}
+
+fn is_parameter_marker(symbol: Symbol) -> bool {
+    matches!(
+        symbol.strip().name().as_ref(),
+        "." | "#!optional" | "#!rest" | "#!key"
+    )
+}
+
+fn is_syntax_keyword(symbol: Symbol, env: &Env) -> bool {
+    matches!(
+        special_form(symbol, env),
+        Some(Operation::DefineSyntax | Operation::LetSyntax | Operation::LetrecSyntax)
+    )
+}
+
+/// Whether `datum` can contain a macro use or definition at all. Most
+/// code doesn't, and is evaluated as parsed.
+fn mentions_syntax(datum: &Datum, env: &Env) -> bool {
+    match datum {
+        Datum::Symbol(symbol) => {
+            is_syntax_keyword(*symbol, env) || matches!(env.lookup(*symbol), Some(Value::Macro(_)))
+        }
+        Datum::List(items) => items.iter().any(|item| mentions_syntax(item, env)),
+        _ => false,
+    }
+}
+
+fn resolve(symbol: Symbol, scope: &Scope, aliases: &Aliases, env: &Env) -> Meaning {
+    match scope.lookup(symbol) {
+        Some(Binding::Variable(renamed)) => return Meaning::Variable(renamed),
+        Some(Binding::Macro(transformer)) => return Meaning::Macro(transformer),
+        None => {}
+    }
+    if let Some(origin) = symbol.origin() {
+        let macro_scope = aliases.borrow().get(&symbol).cloned().unwrap_or_default();
+        return resolve(origin, &macro_scope, aliases, env);
+    }
+    if let Some(Value::Macro(transformer)) = &env.lookup(symbol) {
+        return Meaning::Macro(transformer.clone());
+    }
+    match special_form(symbol, env) {
+        Some(operation) => Meaning::SpecialForm(operation),
+        None => Meaning::Variable(symbol),
+    }
+}
+
+impl Drop for Expander {
+    fn drop(&mut self) {
+        for alias in self.aliases.borrow().keys() {
+            if !self.kept.contains(alias) {
+                alias.free();
+            }
+        }
+    }
+}
+
+impl Expander {
+    fn new(env: &Env) -> Self {
+        Expander {
+            env: env.clone(),
+            aliases: Aliases::default(),
+            renames: RefCell::default(),
+            kept: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    fn resolve(&self, symbol: Symbol, scope: &Scope) -> Meaning {
+        resolve(symbol, scope, &self.aliases, &self.env)
+    }
+
+    /// Gives local variables fresh names, so neither the variables a macro
+    /// inserts nor those around its use can capture each other.
+    fn rename(&self, symbol: Symbol) -> Symbol {
+        let symbol = symbol.strip();
+        let mut renames = self.renames.borrow_mut();
+        let count = renames.entry(symbol).or_default();
+        *count += 1;
+        symbol.renamed(*count - 1)
+    }
+
+    /// Binds a variable for `define`, locally to the innermost frame and
+    /// under its own name at the top level.
+    fn declare(&self, symbol: Symbol, scope: &Scope) -> Symbol {
+        if scope.is_top_level() {
+            return symbol.strip();
+        }
+        match scope.local(symbol) {
+            Some(Binding::Variable(renamed)) => renamed,
+            _ => {
+                let renamed = self.rename(symbol);
+                scope.bind(symbol, Binding::Variable(renamed));
+                renamed
+            }
+        }
+    }
+
+    fn expand(&mut self, datum: &Datum, scope: &Scope) -> Result<Datum> {
+        if self.depth == MAX_DEPTH {
+            return Err(InterpreterError::SyntaxError(datum.clone()));
+        }
+        self.depth += 1;
+        let expanded = self.expand_form(datum, scope);
+        self.depth -= 1;
+        expanded
+    }
+
+    fn expand_form(&mut self, datum: &Datum, scope: &Scope) -> Result<Datum> {
+        let items = match datum {
+            Datum::Symbol(symbol) => {
+                return match self.resolve(*symbol, scope) {
+                    Meaning::Variable(renamed) => Ok(Datum::Symbol(renamed)),
+                    Meaning::SpecialForm(_) => Ok(Datum::Symbol(symbol.strip())),
+                    Meaning::Macro(_) => Err(InterpreterError::SyntaxError(datum.clone())),
+                }
+            }
+            Datum::List(items) => items,
+            other => return Ok(other.clone()),
+        };
+        if let Some(Datum::Symbol(head)) = items.first() {
+            match self.resolve(*head, scope) {
+                Meaning::Macro(transformer) => {
+                    let expansion = self.transcribe(&transformer, items, scope)?;
+                    return self.expand(&expansion, scope);
+                }
+                Meaning::SpecialForm(operation) => {
+                    return self.expand_special_form(operation, items, scope)
+                }
+                Meaning::Variable(_) => {}
+            }
+        }
+        self.expand_all(items, scope).map(Datum::List)
+    }
+
+    fn expand_all(&mut self, items: &[Datum], scope: &Scope) -> Result<Rc<[Datum]>> {
+        items.iter().map(|item| self.expand(item, scope)).collect()
+    }
+
+    /// Expands the forms of a body. Definitions are found first, so every
+    /// form of the body sees all of them.
+    fn expand_body(&mut self, forms: &[Datum], scope: &Scope) -> Result<Vec<Datum>> {
+        let mut forms = forms.to_vec();
+        for form in forms.iter_mut() {
+            while let Datum::List(items) = form {
+                let head = match items.first() {
+                    Some(Datum::Symbol(head)) => *head,
+                    _ => break,
+                };
+                match self.resolve(head, scope) {
+                    Meaning::Macro(transformer) => {
+                        *form = self.transcribe(&transformer, items, scope)?
+                    }
+                    Meaning::SpecialForm(Operation::Define) => {
+                        match items.get(1) {
+                            Some(Datum::Symbol(name)) => {
+                                self.declare(*name, scope);
+                            }
+                            Some(Datum::List(signature)) => {
+                                if let Some(Datum::Symbol(name)) = signature.first() {
+                                    self.declare(*name, scope);
+                                }
+                            }
+                            _ => {}
+                        }
+                        break;
+                    }
+                    Meaning::SpecialForm(Operation::DefineSyntax) => {
+                        self.define_syntax(items, scope)?;
+                        *form = Datum::Nil;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        forms.iter().map(|form| self.expand(form, scope)).collect()
+    }
+
+    fn expand_special_form(
+        &mut self,
+        operation: Operation,
+        items: &Rc<[Datum]>,
+        scope: &Scope,
+    ) -> Result<Datum> {
+        let keyword = match &items[0] {
+            Datum::Symbol(keyword) => Datum::Symbol(keyword.strip()),
+            _ => return Err(invalid_syntax(items)),
+        };
+        let mut expanded = vec![keyword];
+        match operation {
+            Operation::Quote => expanded.extend(items[1..].iter().map(strip_datum)),
+            Operation::Lambda => {
+                // example: (lambda (x y . rest) (+ x y))
+                let parameters = items.get(1).ok_or_else(|| invalid_syntax(items))?;
+                return self.expand_lambda(parameters, &items[2..], scope);
+            }
+            Operation::CaseLambda => {
+                // example: (case-lambda ((x) x) ((x . rest) rest))
+                for clause in items[1..].iter() {
+                    match clause {
+                        Datum::List(clause) if !clause.is_empty() => {
+                            match self.expand_lambda(&clause[0], &clause[1..], scope)? {
+                                Datum::List(lambda) => {
+                                    expanded.push(Datum::List(lambda[1..].into()))
+                                }
+                                _ => return Err(invalid_syntax(items)),
+                            }
+                        }
+                        _ => return Err(invalid_syntax(items)),
+                    }
+                }
+            }
+            Operation::Let => {
+                // example: (let ((a 5) (b (+ 5 a))) (+ a b))
+                let bindings = match items.get(1) {
+                    Some(Datum::List(bindings)) => bindings,
+                    _ => return Err(invalid_syntax(items)),
+                };
+                // the names are bound one after the other, so each init
+                // sees the names before it but not its own
+                let inner = scope.extend();
+                let mut expanded_bindings = Vec::new();
+                for binding in bindings.iter() {
+                    match binding {
+                        Datum::List(binding) => match binding.first() {
+                            Some(Datum::Symbol(name)) => {
+                                let mut expanded_binding =
+                                    self.expand_all(&binding[1..], &inner)?.to_vec();
+                                let renamed = self.rename(*name);
+                                inner.bind(*name, Binding::Variable(renamed));
+                                expanded_binding.insert(0, Datum::Symbol(renamed));
+                                expanded_bindings.push(Datum::List(expanded_binding.into()));
+                            }
+                            _ => return Err(invalid_syntax(items)),
+                        },
+                        _ => return Err(invalid_syntax(items)),
+                    }
+                }
+                expanded.push(Datum::List(expanded_bindings.into()));
+                expanded.extend(self.expand_body(&items[2..], &inner)?);
+            }
+            Operation::Define if matches!(items.get(1), Some(Datum::List(_))) => {
+                // example: (define (add x y) (+ x y))
+                let (name, parameters) = match items.get(1) {
+                    Some(Datum::List(signature)) => match signature.split_first() {
+                        Some((Datum::Symbol(name), parameters)) => (*name, parameters),
+                        _ => return Err(invalid_syntax(items)),
+                    },
+                    _ => return Err(invalid_syntax(items)),
+                };
+                expanded.push(Datum::Symbol(self.declare(name, scope)));
+                let parameters = Datum::List(parameters.into());
+                expanded.push(self.expand_lambda(&parameters, &items[2..], scope)?);
+            }
+            Operation::Define | Operation::Set => {
+                let name = match items.get(1) {
+                    Some(Datum::Symbol(name)) => *name,
+                    _ => return Err(invalid_syntax(items)),
+                };
+                let name = match operation {
+                    Operation::Define => self.declare(name, scope),
+                    _ => match self.resolve(name, scope) {
+                        Meaning::Variable(renamed) => renamed,
+                        _ => return Err(invalid_syntax(items)),
+                    },
+                };
+                expanded.push(Datum::Symbol(name));
+                expanded.extend(self.expand_all(&items[2..], scope)?.iter().cloned());
+            }
+            Operation::Begin => expanded.extend(self.expand_body(&items[1..], &scope.extend())?),
+            Operation::Module => expanded.extend(self.expand_body(&items[1..], scope)?),
+            Operation::Cond => {
+                for clause in items[1..].iter() {
+                    match clause {
+                        Datum::List(clause) => {
+                            expanded.push(Datum::List(self.expand_all(clause, scope)?))
+                        }
+                        _ => return Err(invalid_syntax(items)),
+                    }
+                }
+            }
+            Operation::Guard => {
+                // example: (guard (e (#t (error-object-message e))) (error "oops"))
+                let (variable, clauses) = match items.get(1) {
+                    Some(Datum::List(clauses)) => match clauses.first() {
+                        Some(Datum::Symbol(variable)) => (*variable, &clauses[1..]),
+                        _ => return Err(invalid_syntax(items)),
+                    },
+                    _ => return Err(invalid_syntax(items)),
+                };
+                let handler_scope = scope.extend();
+                let renamed = self.rename(variable);
+                handler_scope.bind(variable, Binding::Variable(renamed));
+                let mut handler = vec![Datum::Symbol(renamed)];
+                for clause in clauses {
+                    match clause {
+                        Datum::List(clause) => {
+                            handler.push(Datum::List(self.expand_all(clause, &handler_scope)?))
+                        }
+                        _ => return Err(invalid_syntax(items)),
+                    }
+                }
+                expanded.push(Datum::List(handler.into()));
+                expanded.extend(self.expand_body(&items[2..], &scope.extend())?);
+            }
+            Operation::DefineSyntax => {
+                self.define_syntax(items, scope)?;
+                return Ok(Datum::Nil);
+            }
+            Operation::LetSyntax | Operation::LetrecSyntax => {
+                // example: (let-syntax ((swap! (syntax-rules () ...))) (swap! a b))
+                let bindings = match items.get(1) {
+                    Some(Datum::List(bindings)) => bindings,
+                    _ => return Err(invalid_syntax(items)),
+                };
+                let inner = scope.extend();
+                let macro_scope = match operation {
+                    Operation::LetSyntax => scope,
+                    _ => &inner,
+                };
+                for binding in bindings.iter() {
+                    match binding {
+                        Datum::List(binding) => match binding.as_ref() {
+                            [Datum::Symbol(name), transformer] => {
+                                let transformer = self.make_macro(transformer, macro_scope)?;
+                                inner.bind(*name, Binding::Macro(transformer));
+                            }
+                            _ => return Err(invalid_syntax(items)),
+                        },
+                        _ => return Err(invalid_syntax(items)),
+                    }
+                }
+                // the body becomes a `begin`, which has its own frame like
+                // the scope of the macros
+                expanded[0] = Datum::Symbol(Symbol::intern("begin"));
+                expanded.extend(self.expand_body(&items[2..], &inner)?);
+            }
+            Operation::SyntaxRules | Operation::ErMacroTransformer => {
+                return Err(invalid_syntax(items))
+            }
+            _ => expanded.extend(self.expand_all(&items[1..], scope)?.iter().cloned()),
+        }
+        Ok(Datum::List(expanded.into()))
+    }
+
+    /// Expands a lambda with its parameters renamed. Markers like `.` and
+    /// `#!optional` are kept, and key parameters keep their names because
+    /// callers pass them by name.
+    fn expand_lambda(
+        &mut self,
+        parameters: &Datum,
+        body: &[Datum],
+        scope: &Scope,
+    ) -> Result<Datum> {
+        let inner = scope.extend();
+        let parameters = match parameters {
+            Datum::List(parameters) => {
+                let mut keys = false;
+                let mut renamed = Vec::new();
+                for parameter in parameters.iter() {
+                    renamed.push(match parameter {
+                        Datum::Symbol(symbol) if is_parameter_marker(*symbol) => {
+                            keys = symbol.strip().name().as_ref() == "#!key";
+                            Datum::Symbol(symbol.strip())
+                        }
+                        Datum::Symbol(symbol) => self.bind_parameter(*symbol, keys, &inner),
+                        Datum::List(spec) => match spec.as_ref() {
+                            [Datum::Symbol(symbol), default] => {
+                                let default = self.expand(default, &inner)?;
+                                let name = self.bind_parameter(*symbol, keys, &inner);
+                                Datum::List([name, default].into())
+                            }
+                            _ => return Err(InterpreterError::SyntaxError(parameter.clone())),
+                        },
+                        _ => return Err(InterpreterError::SyntaxError(parameter.clone())),
+                    });
+                }
+                Datum::List(renamed.into())
+            }
+            Datum::Symbol(rest) => self.bind_parameter(*rest, false, &inner),
+            _ => return Err(InterpreterError::SyntaxError(parameters.clone())),
+        };
+        let mut expanded = vec![Datum::Symbol(Symbol::intern("lambda")), parameters];
+        expanded.extend(self.expand_body(body, &inner)?);
+        Ok(Datum::List(expanded.into()))
+    }
+
+    fn bind_parameter(&self, parameter: Symbol, keep_name: bool, scope: &Scope) -> Datum {
+        let renamed = match keep_name {
+            true => parameter.strip(),
+            false => self.rename(parameter),
+        };
+        scope.bind(parameter, Binding::Variable(renamed));
+        Datum::Symbol(renamed)
+    }
+
+    /// Binds the macro of a `define-syntax`, in the environment if it is at
+    /// the top level. There it outlives the expansion, and so do the aliases
+    /// in its rules.
+    fn define_syntax(&mut self, items: &[Datum], scope: &Scope) -> Result<()> {
+        match items {
+            [_, Datum::Symbol(name), transformer] => {
+                let transformer = self.make_macro(transformer, scope)?;
+                match scope.is_top_level() {
+                    true => {
+                        let mut symbols = Vec::new();
+                        items
+                            .iter()
+                            .for_each(|item| template_symbols(item, &mut symbols));
+                        for mut symbol in symbols {
+                            while let Some(origin) = symbol.origin() {
+                                self.kept.push(symbol);
+                                symbol = origin;
+                            }
+                        }
+                        self.env
+                            .define_symbol(name.strip(), Value::Macro(transformer))
+                    }
+                    false => scope.bind(*name, Binding::Macro(transformer)),
+                }
+                Ok(())
+            }
+            _ => Err(invalid_syntax(items)),
+        }
+    }
+
+    /// Builds the macro of a `syntax-rules` or `er-macro-transformer` form.
+    fn make_macro(&mut self, transformer: &Datum, scope: &Scope) -> Result<Rc<Macro>> {
+        let items = match transformer {
+            Datum::List(items) => items,
+            _ => return Err(InterpreterError::SyntaxError(transformer.clone())),
+        };
+        let keyword = match items.first() {
+            Some(Datum::Symbol(keyword)) => self.resolve(*keyword, scope),
+            _ => return Err(invalid_syntax(items)),
+        };
+        match keyword {
+            Meaning::SpecialForm(Operation::SyntaxRules) => self
+                .make_syntax_rules(items, scope)
+                .map(|rules| Rc::new(Macro::SyntaxRules(rules))),
+            Meaning::SpecialForm(Operation::ErMacroTransformer) => match items.as_ref() {
+                // the procedure is evaluated now, where only the top level
+                // bindings exist
+                [_, procedure] => Ok(Rc::new(Macro::ExplicitRenaming {
+                    procedure: eval(&mut self.env.clone(), procedure)?,
+                    scope: scope.clone(),
+                })),
+                _ => Err(invalid_syntax(items)),
+            },
+            _ => Err(invalid_syntax(items)),
+        }
+    }
+
+    /// A `syntax-rules` form may name its own ellipsis before the literals.
+    fn make_syntax_rules(&mut self, items: &[Datum], scope: &Scope) -> Result<SyntaxRules> {
+        let (ellipsis, rest) = match &items[1..] {
+            [Datum::Symbol(ellipsis), rest @ ..] => (*ellipsis, rest),
+            rest => (Symbol::intern("..."), rest),
+        };
+        let literals = match rest.first() {
+            Some(Datum::List(literals)) => literals
+                .iter()
+                .map(|literal| match literal {
+                    Datum::Symbol(literal) => Ok(*literal),
+                    _ => Err(invalid_syntax(items)),
+                })
+                .collect::<Result<_>>()?,
+            _ => return Err(invalid_syntax(items)),
+        };
+        let rules = rest[1..]
+            .iter()
+            .map(|rule| match rule {
+                Datum::List(rule) => match rule.as_ref() {
+                    [Datum::List(pattern), template] if !pattern.is_empty() => {
+                        Ok((pattern.clone(), template.clone()))
+                    }
+                    _ => Err(invalid_syntax(items)),
+                },
+                _ => Err(invalid_syntax(items)),
+            })
+            .collect::<Result<_>>()?;
+        Ok(SyntaxRules {
+            literals,
+            ellipsis,
+            rules,
+            scope: scope.clone(),
+        })
+    }
+
+    fn transcribe(&mut self, transformer: &Macro, form: &[Datum], scope: &Scope) -> Result<Datum> {
+        match transformer {
+            Macro::SyntaxRules(rules) => self.transcribe_rules(rules, form, scope),
+            Macro::ExplicitRenaming {
+                procedure,
+                scope: macro_scope,
+            } => self.transcribe_explicit(procedure, macro_scope, form, scope),
+        }
+    }
+
+    /// Calls the procedure of an explicit renaming macro. `rename` makes
+    /// aliases that refer to the bindings where the macro was defined, and
+    /// `compare` tells whether two identifiers mean the same at the use.
+    fn transcribe_explicit(
+        &mut self,
+        procedure: &Value,
+        macro_scope: &Scope,
+        form: &[Datum],
+        scope: &Scope,
+    ) -> Result<Datum> {
+        let renames = Rc::new(RefCell::new(SymbolMap::default()));
+        let aliases = self.aliases.clone();
+        let macro_scope = macro_scope.clone();
+        let rename = Value::native("rename", move |arguments| match arguments {
+            [Value::Symbol(symbol)] => {
+                let alias = *renames.borrow_mut().entry(*symbol).or_insert_with(|| {
+                    let alias = symbol.alias();
+                    aliases.borrow_mut().insert(alias, macro_scope.clone());
+                    alias
+                });
+                Ok(Value::Symbol(alias))
+            }
+            _ => Err(InterpreterError::ArgumentError(
+                "rename takes a symbol".into(),
+            )),
+        });
+        let (aliases, env, scope) = (self.aliases.clone(), self.env.clone(), scope.clone());
+        let compare = Value::native("compare", move |arguments| match arguments {
+            [Value::Symbol(left), Value::Symbol(right)] => Ok(Value::Bool(
+                resolve(*left, &scope, &aliases, &env) == resolve(*right, &scope, &aliases, &env),
+            )),
+            [_, _] => Ok(Value::Bool(false)),
+            _ => Err(InterpreterError::ArgumentError(
+                "compare takes two identifiers".into(),
+            )),
+        });
+        let form = Value::from(&Datum::List(form.into()));
+        apply_procedure(&self.env, procedure.clone(), &[form, rename, compare])?.to_datum()
+    }
+
+    /// Rewrites a macro use with the first rule whose pattern matches it.
+    /// The keyword in the pattern is ignored.
+    fn transcribe_rules(
+        &mut self,
+        transformer: &SyntaxRules,
+        form: &[Datum],
+        scope: &Scope,
+    ) -> Result<Datum> {
+        for (pattern, template) in &transformer.rules {
+            let mut matches = Matches::default();
+            if self.match_list(transformer, &pattern[1..], &form[1..], scope, &mut matches) {
+                let mut aliases = SymbolMap::default();
+                return self.instantiate(transformer, template, &matches, &mut aliases, true);
+            }
+        }
+        Err(invalid_syntax(form))
+    }
+
+    fn match_pattern(
+        &self,
+        transformer: &SyntaxRules,
+        pattern: &Datum,
+        form: &Datum,
+        scope: &Scope,
+        matches: &mut Matches,
+    ) -> bool {
+        match pattern {
+            Datum::Symbol(symbol) if symbol.strip() == Symbol::intern("_") => true,
+            Datum::Symbol(literal) if transformer.literals.contains(literal) => match form {
+                // a literal matches identifiers that mean the same
+                Datum::Symbol(symbol) => {
+                    self.resolve(*symbol, scope) == self.resolve(*literal, &transformer.scope)
+                }
+                _ => false,
+            },
+            Datum::Symbol(variable) => {
+                matches.insert(*variable, Matched::One(form.clone()));
+                true
+            }
+            Datum::List(patterns) => match form {
+                Datum::List(forms) => self.match_list(transformer, patterns, forms, scope, matches),
+                _ => false,
+            },
+            literal => literal == form,
+        }
+    }
+
+    fn match_list(
+        &self,
+        transformer: &SyntaxRules,
+        patterns: &[Datum],
+        forms: &[Datum],
+        scope: &Scope,
+        matches: &mut Matches,
+    ) -> bool {
+        let ellipsis = Datum::Symbol(transformer.ellipsis);
+        let (before, repeated, after) = match patterns.iter().position(|item| *item == ellipsis) {
+            Some(position) if position > 0 => (
+                &patterns[..position - 1],
+                &patterns[position - 1],
+                &patterns[position + 1..],
+            ),
+            _ => {
+                return patterns.len() == forms.len()
+                    && patterns.iter().zip(forms).all(|(pattern, form)| {
+                        self.match_pattern(transformer, pattern, form, scope, matches)
+                    })
+            }
+        };
+        if forms.len() < before.len() + after.len() {
+            return false;
+        }
+        let repeated_forms = &forms[before.len()..forms.len() - after.len()];
+        if !self.match_list(transformer, before, &forms[..before.len()], scope, matches)
+            || !self.match_list(
+                transformer,
+                after,
+                &forms[forms.len() - after.len()..],
+                scope,
+                matches,
+            )
+        {
+            return false;
+        }
+        let mut variables = Vec::new();
+        pattern_variables(transformer, repeated, &mut variables);
+        let mut repetitions: Vec<Vec<Matched>> = vec![Vec::new(); variables.len()];
+        for form in repeated_forms {
+            let mut inner = Matches::default();
+            if !self.match_pattern(transformer, repeated, form, scope, &mut inner) {
+                return false;
+            }
+            for (variable, repetition) in variables.iter().zip(repetitions.iter_mut()) {
+                if let Some(matched) = inner.remove(variable) {
+                    repetition.push(matched);
+                }
+            }
+        }
+        for (variable, repetition) in variables.into_iter().zip(repetitions) {
+            matches.insert(variable, Matched::Many(repetition));
+        }
+        true
+    }
+
+    /// Fills in a template. Symbols that aren't pattern variables are
+    /// inserted as aliases, the same alias for the same symbol throughout
+    /// one expansion.
+    fn instantiate(
+        &mut self,
+        transformer: &SyntaxRules,
+        template: &Datum,
+        matches: &Matches,
+        aliases: &mut SymbolMap<Symbol>,
+        with_ellipsis: bool,
+    ) -> Result<Datum> {
+        match template {
+            Datum::Symbol(symbol) => match matches.get(symbol) {
+                Some(Matched::One(form)) => Ok(form.clone()),
+                Some(Matched::Many(_)) => Err(InterpreterError::SyntaxError(template.clone())),
+                None => {
+                    let alias = *aliases.entry(*symbol).or_insert_with(|| symbol.alias());
+                    self.aliases
+                        .borrow_mut()
+                        .insert(alias, transformer.scope.clone());
+                    Ok(Datum::Symbol(alias))
+                }
+            },
+            Datum::List(items) => {
+                let ellipsis = Datum::Symbol(transformer.ellipsis);
+                if with_ellipsis && items.len() == 2 && items[0] == ellipsis {
+                    // (... ...) stands for the ellipsis itself
+                    return self.instantiate(transformer, &items[1], matches, aliases, false);
+                }
+                let mut expanded = Vec::new();
+                let mut index = 0;
+                while index < items.len() {
+                    let mut depth = 0;
+                    while with_ellipsis && items.get(index + depth + 1) == Some(&ellipsis) {
+                        depth += 1;
+                    }
+                    match depth {
+                        0 => expanded.push(self.instantiate(
+                            transformer,
+                            &items[index],
+                            matches,
+                            aliases,
+                            with_ellipsis,
+                        )?),
+                        _ => self.instantiate_repeated(
+                            transformer,
+                            &items[index],
+                            depth,
+                            matches,
+                            aliases,
+                            &mut expanded,
+                        )?,
+                    }
+                    index += depth + 1;
+                }
+                Ok(Datum::List(expanded.into()))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+
+    /// Fills in a template followed by `depth` ellipses once for every
+    /// repetition of the pattern variables in it.
+    fn instantiate_repeated(
+        &mut self,
+        transformer: &SyntaxRules,
+        template: &Datum,
+        depth: usize,
+        matches: &Matches,
+        aliases: &mut SymbolMap<Symbol>,
+        expanded: &mut Vec<Datum>,
+    ) -> Result<()> {
+        let mut symbols = Vec::new();
+        template_symbols(template, &mut symbols);
+        let repeated: Vec<(Symbol, &Vec<Matched>)> = symbols
+            .into_iter()
+            .filter_map(|symbol| match matches.get(&symbol) {
+                Some(Matched::Many(repetitions)) => Some((symbol, repetitions)),
+                _ => None,
+            })
+            .collect();
+        let count = match repeated.first() {
+            Some((_, repetitions)) => repetitions.len(),
+            None => return Err(InterpreterError::SyntaxError(template.clone())),
+        };
+        if repeated
+            .iter()
+            .any(|(_, repetitions)| repetitions.len() != count)
+        {
+            return Err(InterpreterError::SyntaxError(template.clone()));
+        }
+        for index in 0..count {
+            let mut inner = matches.clone();
+            for (symbol, repetitions) in &repeated {
+                inner.insert(*symbol, repetitions[index].clone());
+            }
+            match depth {
+                1 => {
+                    expanded.push(self.instantiate(transformer, template, &inner, aliases, true)?)
+                }
+                _ => self.instantiate_repeated(
+                    transformer,
+                    template,
+                    depth - 1,
+                    &inner,
+                    aliases,
+                    expanded,
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pattern_variables(transformer: &SyntaxRules, pattern: &Datum, variables: &mut Vec<Symbol>) {
+    match pattern {
+        Datum::Symbol(symbol)
+            if *symbol != transformer.ellipsis
+                && symbol.strip() != Symbol::intern("_")
+                && !transformer.literals.contains(symbol) =>
+        {
+            variables.push(*symbol)
+        }
+        Datum::List(items) => items
+            .iter()
+            .for_each(|item| pattern_variables(transformer, item, variables)),
+        _ => {}
+    }
+}
+
+fn template_symbols(template: &Datum, symbols: &mut Vec<Symbol>) {
+    match template {
+        Datum::Symbol(symbol) => symbols.push(*symbol),
+        Datum::List(items) => items
+            .iter()
+            .for_each(|item| template_symbols(item, symbols)),
+        _ => {}
+    }
+}
+
+/// Expands the macros in `datum` into the special forms and calls `eval`
+/// understands. Local variables are renamed to fresh symbols on the way,
+/// which keeps macros hygienic.
+pub fn expand(env: &Env, datum: &Datum) -> Result<Datum> {
+    if !mentions_syntax(datum, env) {
+        return Ok(datum.clone());
+    }
+    Expander::new(env).expand(datum, &Scope::default())
+}
+
+/// Expands `datum` if it is a macro use, once or until it isn't one
+/// anymore, and leaves its subforms alone. Aliases are stripped, so the
+/// result reads like code but loses the hygiene of the expansion.
+pub fn macroexpand(env: &Env, datum: &Datum, once: bool) -> Result<Datum> {
+    let mut expander = Expander::new(env);
+    let scope = Scope::default();
+    let mut datum = datum.clone();
+    while let Datum::List(items) = &datum {
+        let transformer = match items.first() {
+            Some(Datum::Symbol(head)) => match expander.resolve(*head, &scope) {
+                Meaning::Macro(transformer) => transformer,
+                _ => break,
+            },
+            _ => break,
+        };
+        datum = expander.transcribe(&transformer, items, &scope)?;
+        if once {
+            break;
+        }
+    }
+    Ok(strip_datum(&datum))
+}
@@ -0,0 +1,193 @@
+use std::iter::Peekable;
+use std::slice::Iter;
+use std::str::Chars;
+
+use crate::error::{InterpreterError, Result};
+use crate::eval::not_a;
+use crate::port::{current_output, print, Style};
+use crate::value::Value;
+
+fn format_error(explanation: String) -> InterpreterError {
+    InterpreterError::ArgumentError(format!("format: {}", explanation))
+}
+
+/// The largest width or precision a directive may have, well past any
+/// column or digit a result would need.
+const MAX_PARAMETER: usize = 1000;
+
+/// The comma separated numbers between a `~` and its directive, like the
+/// width and precision of `~8,3F`.
+fn parameters(chars: &mut Peekable<Chars>) -> Result<Vec<Option<usize>>> {
+    let mut text = String::new();
+    while let Some(char) = chars.next_if(|char| char.is_ascii_digit() || *char == ',') {
+        text.push(char);
+    }
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    text.split(',')
+        .map(|parameter| match parameter {
+            "" => Ok(None),
+            parameter => match parameter.parse() {
+                Ok(value) if value <= MAX_PARAMETER => Ok(Some(value)),
+                _ => Err(format_error(format!(
+                    "parameter {} is not a number up to {}",
+                    parameter, MAX_PARAMETER
+                ))),
+            },
+        })
+        .collect()
+}
+
+/// Pads `text` with spaces to `width` characters, on the left for numbers
+/// so that they line up on the right.
+fn pad(out: &mut String, text: &str, width: Option<usize>, left: bool) {
+    let padding = " ".repeat(width.unwrap_or(0).saturating_sub(text.chars().count()));
+    match left {
+        true => {
+            out.push_str(&padding);
+            out.push_str(text);
+        }
+        false => {
+            out.push_str(text);
+            out.push_str(&padding);
+        }
+    }
+}
+
+fn integer(value: &Value, radix: u32) -> Result<String> {
+    let Value::Int(integer) = value else {
+        return Err(format_error(format!("{} is not an integer", value)));
+    };
+    let magnitude = integer.unsigned_abs();
+    let digits = match radix {
+        16 => format!("{:x}", magnitude),
+        8 => format!("{:o}", magnitude),
+        2 => format!("{:b}", magnitude),
+        _ => magnitude.to_string(),
+    };
+    Ok(match *integer < 0 {
+        true => format!("-{}", digits),
+        false => digits,
+    })
+}
+
+fn fixed(value: &Value, precision: Option<usize>) -> Result<String> {
+    let number = match value {
+        Value::Int(integer) => *integer as f64,
+        Value::Float(float) => *float,
+        // strings are only padded
+        Value::Str(string) => return Ok(string.borrow().clone()),
+        value => return Err(format_error(format!("{} is not a number", value))),
+    };
+    Ok(match precision {
+        Some(precision) => format!("{:.*}", precision, number),
+        None => value.to_string(),
+    })
+}
+
+/// Appends `template` to `out` with its directives replaced by the
+/// `arguments` they consume.
+fn format_into(out: &mut String, template: &str, arguments: &mut Iter<Value>) -> Result<()> {
+    let mut chars = template.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char != '~' {
+            out.push(char);
+            continue;
+        }
+        let parameters = parameters(&mut chars)?;
+        let width = parameters.first().copied().flatten();
+        let precision = parameters.get(1).copied().flatten();
+        let directive = chars
+            .next()
+            .ok_or_else(|| format_error("template ends in ~".into()))?;
+        let mut argument = || {
+            arguments
+                .next()
+                .ok_or_else(|| format_error(format!("missing argument for ~{}", directive)))
+        };
+        match directive.to_ascii_lowercase() {
+            directive @ ('a' | 's' | 'w' | 'y') => {
+                let style = match directive {
+                    'a' => Style::Display,
+                    'w' => Style::Shared,
+                    _ => Style::Write,
+                };
+                let mut text = String::new();
+                print(&mut text, argument()?, style);
+                pad(out, &text, width, false);
+            }
+            directive @ ('d' | 'x' | 'o' | 'b') => {
+                let radix = match directive {
+                    'x' => 16,
+                    'o' => 8,
+                    'b' => 2,
+                    _ => 10,
+                };
+                pad(out, &integer(argument()?, radix)?, width, true);
+            }
+            'f' => pad(out, &fixed(argument()?, precision)?, width, true),
+            'c' => match argument()? {
+                Value::Char(char) => out.push(*char),
+                value => return Err(format_error(format!("{} is not a character", value))),
+            },
+            '?' => {
+                let Value::Str(template) = argument()? else {
+                    return Err(format_error("~? takes a template string".into()));
+                };
+                let template = template.borrow().clone();
+                let nested = argument()?.to_vec()?;
+                let mut nested_arguments = nested.iter();
+                format_into(out, &template, &mut nested_arguments)?;
+                if nested_arguments.next().is_some() {
+                    return Err(format_error("too many arguments for ~?".into()));
+                }
+            }
+            '%' | 'n' => out.push('\n'),
+            '&' => {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            '_' => out.push(' '),
+            '~' => out.push('~'),
+            '\n' => while chars.next_if(|char| char.is_whitespace()).is_some() {},
+            directive => return Err(format_error(format!("unknown directive ~{}", directive))),
+        }
+    }
+    Ok(())
+}
+
+/// `(format [destination] template argument ...)`, SRFI-28 and most of
+/// SRFI-48. The destination is `#f` or missing to return a string, `#t`
+/// for the current output port, or a port.
+pub fn apply_format(arguments: &[Value]) -> Result<Value> {
+    let (destination, arguments) = match &arguments[0] {
+        Value::Str(_) => (None, arguments),
+        Value::Bool(false) => (None, &arguments[1..]),
+        Value::Bool(true) => (Some(current_output()), &arguments[1..]),
+        Value::Port(port) => (Some(port.clone()), &arguments[1..]),
+        value => return Err(not_a("format destination", value)),
+    };
+    let template = match arguments.first() {
+        Some(Value::Str(template)) => template.borrow().clone(),
+        Some(value) => return Err(not_a("template string", value)),
+        None => return Err(format_error("missing template".into())),
+    };
+    let mut out = String::new();
+    let mut arguments = arguments[1..].iter();
+    format_into(&mut out, &template, &mut arguments)?;
+    if arguments.len() > 0 {
+        return Err(format_error(format!(
+            "{} arguments left over",
+            arguments.len()
+        )));
+    }
+    match destination {
+        Some(port) => {
+            port.write_str(&out)?;
+            Ok(Value::Nil)
+        }
+        None => Ok(Value::string(out)),
+    }
+}
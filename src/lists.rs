@@ -0,0 +1,211 @@
+use std::rc::Rc;
+
+use crate::error::{InterpreterError, Result};
+use crate::eval::reserve;
+use crate::value::{Operation, Value};
+
+fn cons(head: Value, tail: Value) -> Value {
+    Value::Cons {
+        head: Rc::new(head),
+        tail: Rc::new(tail),
+    }
+}
+
+/// Builds a list of `elements` that ends in `tail` instead of the empty
+/// list when `tail` isn't one.
+fn list_with_tail(elements: impl DoubleEndedIterator<Item = Value>, tail: Value) -> Value {
+    elements.rfold(tail, |tail, head| cons(head, tail))
+}
+
+/// The elements of a list, proper or not, and what its last pair points to.
+fn spine(list: &Value) -> (Vec<Value>, Value) {
+    let mut elements = Vec::new();
+    let mut current = list;
+    while let Value::Cons { head, tail } = current {
+        elements.push(head.as_ref().clone());
+        current = tail;
+    }
+    (elements, current.clone())
+}
+
+fn index(value: &Value) -> Result<usize> {
+    match value {
+        Value::Int(index) => usize::try_from(*index)
+            .map_err(|_| InterpreterError::ValueError(format!("{} is not a valid index", index))),
+        value => Err(InterpreterError::ValueError(format!(
+            "{} is not a valid index",
+            value
+        ))),
+    }
+}
+
+fn list_tail(name: &str, list: &Value, count: usize) -> Result<Value> {
+    let mut current = list;
+    for _ in 0..count {
+        match current {
+            Value::Cons { tail, .. } => current = tail,
+            _ => {
+                return Err(InterpreterError::ValueError(format!(
+                    "{}: {} has fewer than {} elements",
+                    name, list, count
+                )))
+            }
+        }
+    }
+    Ok(current.clone())
+}
+
+/// How `assoc` and `member` compare elements: `==` or `Value::is_eqv`.
+type Same = fn(&Value, &Value) -> bool;
+
+/// The first pair of `alist` whose car is the same as `key`.
+fn assoc(key: &Value, alist: &Value, same: Same) -> Result<Value> {
+    for entry in alist.to_vec()? {
+        match &entry {
+            Value::Cons { head, .. } if same(head, key) => return Ok(entry),
+            Value::Cons { .. } => {}
+            entry => {
+                return Err(InterpreterError::ValueError(format!(
+                    "association list entry {} is not a pair",
+                    entry
+                )))
+            }
+        }
+    }
+    Ok(Value::Bool(false))
+}
+
+/// The first tail of `list` that starts with `element`.
+fn member(element: &Value, list: &Value, same: Same) -> Result<Value> {
+    let mut current = list;
+    while let Value::Cons { head, tail } = current {
+        if same(head, element) {
+            return Ok(current.clone());
+        }
+        current = tail;
+    }
+    match current {
+        Value::Nil => Ok(Value::Bool(false)),
+        _ => Err(InterpreterError::ValueError(format!(
+            "{} is not a proper list",
+            list
+        ))),
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(value) => Some(*value as f64),
+        Value::Float(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn iota(arguments: &[Value]) -> Result<Value> {
+    let count = index(&arguments[0])?;
+    let start = arguments.get(1).cloned().unwrap_or(Value::Int(0));
+    let step = arguments.get(2).cloned().unwrap_or(Value::Int(1));
+    let mut numbers = reserve(count)?;
+    match (start, step) {
+        (Value::Int(start), Value::Int(step)) => {
+            for index in 0..count {
+                let number = (index as i128)
+                    .checked_mul(step)
+                    .and_then(|offset| offset.checked_add(start))
+                    .ok_or_else(|| {
+                        InterpreterError::RuntimeError("integer overflow in iota".into())
+                    })?;
+                numbers.push(Value::Int(number));
+            }
+        }
+        (start, step) => match (as_float(&start), as_float(&step)) {
+            (Some(start), Some(step)) => {
+                numbers.extend((0..count).map(|index| Value::Float(start + index as f64 * step)))
+            }
+            _ => {
+                return Err(InterpreterError::ValueError(
+                    "iota takes a count, a start and a step number".into(),
+                ))
+            }
+        },
+    }
+    Ok(Value::list(numbers.into_iter()))
+}
+
+/// Applies the SRFI-1 procedures that don't call a procedure argument.
+pub fn apply_list_primitive(operation: Operation, arguments: &[Value]) -> Result<Value> {
+    match operation {
+        Operation::Append => match arguments.split_last() {
+            Some((last, lists)) => lists.iter().try_rfold(last.clone(), |tail, list| {
+                Ok(list_with_tail(list.to_vec()?.into_iter(), tail))
+            }),
+            None => Ok(Value::Nil),
+        },
+        Operation::Reverse => Ok(Value::list(arguments[0].to_vec()?.into_iter().rev())),
+        Operation::Length => Ok(Value::Int(arguments[0].to_vec()?.len() as i128)),
+        Operation::ListTail | Operation::Drop => {
+            list_tail(operation.name(), &arguments[0], index(&arguments[1])?)
+        }
+        Operation::ListRef => match &list_tail("list-ref", &arguments[0], index(&arguments[1])?)? {
+            Value::Cons { head, .. } => Ok(head.as_ref().clone()),
+            _ => Err(InterpreterError::ValueError(format!(
+                "list-ref: index {} out of range",
+                arguments[1]
+            ))),
+        },
+        Operation::Take => {
+            let count = index(&arguments[1])?;
+            let (elements, _) = spine(&arguments[0]);
+            if elements.len() < count {
+                return Err(InterpreterError::ValueError(format!(
+                    "take: {} has fewer than {} elements",
+                    arguments[0], count
+                )));
+            }
+            Ok(Value::list(elements.into_iter().take(count)))
+        }
+        Operation::ListCopy => {
+            let (elements, tail) = spine(&arguments[0]);
+            Ok(list_with_tail(elements.into_iter(), tail))
+        }
+        Operation::LastPair => {
+            let mut current = &arguments[0];
+            while let Value::Cons { tail, .. } = current {
+                if !matches!(tail.as_ref(), Value::Cons { .. }) {
+                    return Ok(current.clone());
+                }
+                current = tail;
+            }
+            Err(InterpreterError::ValueError(format!(
+                "last-pair: {} is not a pair",
+                current
+            )))
+        }
+        Operation::Iota => iota(arguments),
+        Operation::Delete => {
+            let elements = arguments[1].to_vec()?;
+            Ok(Value::list(
+                elements
+                    .into_iter()
+                    .filter(|element| *element != arguments[0]),
+            ))
+        }
+        Operation::DeleteDuplicates => {
+            let mut kept: Vec<Value> = Vec::new();
+            for element in arguments[0].to_vec()? {
+                if !kept.contains(&element) {
+                    kept.push(element);
+                }
+            }
+            Ok(Value::list(kept.into_iter()))
+        }
+        Operation::Assq | Operation::Assv => assoc(&arguments[0], &arguments[1], Value::is_eqv),
+        Operation::Assoc => assoc(&arguments[0], &arguments[1], Value::eq),
+        Operation::Memq | Operation::Memv => member(&arguments[0], &arguments[1], Value::is_eqv),
+        Operation::Member => member(&arguments[0], &arguments[1], Value::eq),
+        _ => Err(InterpreterError::RuntimeError(format!(
+            "{} is not a list primitive",
+            operation.name()
+        ))),
+    }
+}
@@ -1,42 +1,71 @@
+mod alloc_counter;
+mod bytevectors;
 mod env;
 mod error;
 mod eval;
+mod expand;
+mod format;
+mod lists;
 mod parse;
+mod port;
+mod regexp;
+mod strings;
+mod symbol;
 mod tokenize;
+mod value;
 
 use std::env as std_env;
 use std::fs;
 use std::io::{self, Write};
+use std::process;
 use std::time::Instant;
 
+use alloc_counter::{count_allocations, CountingAllocator};
 use env::Env;
-use error::Result;
+use error::{InterpreterError, Result};
 use eval::eval;
-use parse::{parse, SymbolicExpression};
+use parse::parse;
+use symbol::Symbol;
 use tokenize::tokenize;
+use value::Value;
 
-fn eval_str(env: &mut Env, code: &str) -> Result<SymbolicExpression> {
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn eval_str(env: &mut Env, code: &str) -> Result<Value> {
     let mut tokens = tokenize(code);
     tokens.pop_front();
-    let expression = parse(&mut tokens);
+    let expression = parse(&mut tokens)?;
     eval(env, &expression)
 }
 
-fn eval_file(env: &mut Env, filename: &str) -> Result<SymbolicExpression> {
-    let contents = fs::read_to_string(filename).expect("Should have been able to read the file");
+fn eval_file(env: &mut Env, filename: &str) -> Result<Value> {
+    let contents = fs::read_to_string(filename).map_err(|error| {
+        InterpreterError::IoError(format!("cannot read {}: {}", filename, error))
+    })?;
     eval_str(env, &contents)
 }
 
 fn repl() {
     let mut env = Env::new();
-    eval_file(&mut env, "std.scm").unwrap();
+    // without std.scm the primitives still work
+    if let Err(err) = eval_file(&mut env, "std.scm") {
+        println!("{}", err);
+    }
     loop {
         print!("repl> ");
-        io::stdout().flush().unwrap();
         let mut line = String::new();
-        io::stdin()
-            .read_line(&mut line)
-            .expect("Failed to read line");
+        match io::stdout()
+            .flush()
+            .and_then(|_| io::stdin().read_line(&mut line))
+        {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{}", InterpreterError::IoError(err.to_string()));
+                process::exit(1);
+            }
+        }
 
         line = line.trim().to_string();
         if line == "exit" {
@@ -54,11 +83,16 @@ fn repl() {
 }
 
 fn benchmark() {
+    // the native map and reduce against the std.scm ones they replaced
     let code_strings = vec![
         "(fib 30)",
-        "(reduce + (map (lambda (x) (* x x)) (range 1000)))",
-        "(reducei + (map (lambda (x) (* x x)) (range 1000)))",
+        "(reduce-recursive + (map-recursive (lambda (x) (* x x)) (range 1000)))",
         "(reducei + (mapi (lambda (x) (* x x)) (range 1000)))",
+        "(reduce + 0 (map (lambda (x) (* x x)) (range 1000)))",
+        "(fold-left + 0 (map (lambda (x) (* x x)) (range 1000)))",
+        "(reduce-recursive + (map-recursive (lambda (x) (* x x)) (range 100000)))",
+        "(reducei + (mapi (lambda (x) (* x x)) (range 100000)))",
+        "(reduce + 0 (map (lambda (x) (* x x)) (range 100000)))",
     ];
 
     for code_string in code_strings {
@@ -66,21 +100,58 @@ fn benchmark() {
         eval_file(&mut env, "std.scm").unwrap();
 
         let now = Instant::now();
-        {
-            let _ = eval_str(&mut env, code_string);
-        }
+        let (_, allocations) = count_allocations(|| eval_str(&mut env, code_string));
         let elapsed = now.elapsed();
-        println!("{} took: {:.2?}", code_string, elapsed);
+        println!(
+            "{} took: {:.2?} ({} allocations)",
+            code_string, elapsed, allocations
+        );
     }
+
+    for code_string in CLONING_BENCHMARKS {
+        for length in [10, 10_000] {
+            println!(
+                "{} on a list of {} took: {} allocations",
+                code_string,
+                length,
+                allocations_with_list(length, code_string)
+            );
+        }
+    }
+}
+
+/// Snippets that pass around, bind or quote the list `xs` and the quoted
+/// list `data`; none of them should allocate more for a longer list.
+const CLONING_BENCHMARKS: [&str; 4] = [
+    "(identity xs)",
+    "(let ((ys xs)) (car ys))",
+    "(identity data)",
+    "(begin (define zs xs) (cdr zs))",
+];
+
+fn allocations_with_list(length: usize, code: &str) -> usize {
+    let mut env = Env::new();
+    let elements = (0..length)
+        .map(|element| element.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    eval_str(&mut env, &format!("(define xs (list {}))", elements)).unwrap();
+    eval_str(&mut env, &format!("(define data (quote ({})))", elements)).unwrap();
+    eval_str(&mut env, "(define identity (lambda (x) x))").unwrap();
+    // intern the symbols of the snippet up front so only evaluation is counted
+    parse(&mut tokenize(code)).unwrap();
+    let (result, allocations) = count_allocations(|| eval_str(&mut env, code));
+    result.unwrap();
+    allocations
 }
 
 fn test() {
     let code = "(mapi (lambda (x) (* x x)) (range 1000))";
     let mut env = Env::new();
     eval_file(&mut env, "std.scm").unwrap();
-    let expression = parse(&mut tokenize(code));
+    let expression = parse(&mut tokenize(code)).unwrap();
     println!("{}", expression);
-    println!("{}", env.find_symbol("mapi").unwrap());
+    println!("{}", env.find_symbol(Symbol::intern("mapi")).unwrap());
 }
 
 fn run_file(filename: &str) {
@@ -108,6 +179,7 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::InterpreterError;
 
     #[test]
     fn simple_define_function() {
@@ -116,11 +188,11 @@ mod tests {
         eval_str(&mut env, "(define circle-area (lambda (r) (* pi (* r r))))").unwrap();
         assert_eq!(
             eval_str(&mut env, "(circle-area 3)").unwrap(),
-            SymbolicExpression::Float(28.274333877)
+            Value::Float(28.274333877)
         );
         assert_eq!(
             eval_str(&mut env, "(circle-area 3)").unwrap(),
-            SymbolicExpression::Float(28.274333877)
+            Value::Float(28.274333877)
         );
     }
 
@@ -138,47 +210,1712 @@ mod tests {
         let code = "(define account (make-account 100.00))";
         eval_str(&mut env, code).unwrap();
         let code = "(account -20.00)";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Float(80.0));
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Float(60.0));
+    }
+
+    #[test]
+    fn fib() {
+        let code =
+            "(define fib (lambda (n) (cond ((< n 2) 1) (#t (+ (fib (- n 1)) (fib (- n 2)))))))";
+        let mut env = Env::new();
+        eval_str(&mut env, code).unwrap();
+        assert_eq!(eval_str(&mut env, "(fib 0)").unwrap(), Value::Int(1));
+        assert_eq!(eval_str(&mut env, "(fib 1)").unwrap(), Value::Int(1));
+        assert_eq!(eval_str(&mut env, "(fib 2)").unwrap(), Value::Int(2));
+        assert_eq!(eval_str(&mut env, "(fib 9)").unwrap(), Value::Int(55));
+    }
+
+    #[test]
+    fn cloning_values_does_not_depend_on_their_size() {
+        for code in CLONING_BENCHMARKS {
+            assert_eq!(
+                allocations_with_list(10, code),
+                allocations_with_list(10_000, code),
+                "{} allocates more for a longer list",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn long_lists_do_not_overflow_the_stack() {
+        let mut env = Env::new();
+        eval_str(&mut env, "(define xs (iota 1000000))").unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(= xs (iota 1000000))").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval_str(&mut env, "(= xs (iota 1000000 1))").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            eval_str(&mut env, "(length xs)").unwrap(),
+            Value::Int(1000000)
+        );
+        let formatted = eval_str(&mut env, "(begin xs)").unwrap().to_string();
+        assert!(formatted.starts_with("(0 . (1 . "));
+        assert!(formatted.trim_end_matches(')').ends_with("(999999 . #nil"));
+        eval_str(&mut env, "(define xs 0)").unwrap();
+    }
+
+    #[test]
+    fn deeply_nested_code_is_an_error() {
+        let mut env = Env::new();
+        let nested = |depth: usize| format!("{}1{}", "(list ".repeat(depth), ")".repeat(depth));
+        let quoted = |depth: usize| format!("(quote {}{})", "(".repeat(depth), ")".repeat(depth));
+        assert!(eval_str(&mut env, &nested(parse::MAX_DEPTH)).is_ok());
+        assert!(eval_str(&mut env, &quoted(parse::MAX_DEPTH - 1)).is_ok());
+        for code in [nested(parse::MAX_DEPTH + 1), quoted(200_000)] {
+            assert!(matches!(
+                eval_str(&mut env, &code),
+                Err(InterpreterError::ReadError(_))
+            ));
+        }
+        let code = "
+        (define (nest depth x) (if (= depth 0) x (nest (- depth 1) (list (quote list) x))))
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert!(eval_str(&mut env, "(eval (nest 100000 1))").is_err());
+        eval_str(
+            &mut env,
+            "(define-syntax again (syntax-rules () ((_) (again))))",
+        )
+        .unwrap();
+        assert!(matches!(
+            eval_str(&mut env, "(again)"),
+            Err(InterpreterError::SyntaxError(_))
+        ));
+    }
+
+    #[test]
+    fn quoted_code_is_data() {
+        let mut env = Env::new();
+        assert_eq!(
+            eval_str(&mut env, "(car (quote (+ 1 2)))").unwrap(),
+            Value::Symbol(Symbol::intern("+"))
+        );
+        assert_eq!(
+            eval_str(&mut env, "(eval (quote (+ 1 2)))").unwrap(),
+            Value::Int(3)
+        );
+    }
+
+    #[test]
+    fn symbols() {
+        let mut env = Env::new();
+        assert_eq!(
+            eval_str(&mut env, "(symbol=? (quote abc) (string->symbol \"abc\"))").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval_str(&mut env, "(symbol->string (quote abc))").unwrap(),
+            Value::string("abc")
+        );
+        eval_str(&mut env, "(define generated (gensym))").unwrap();
+        assert_eq!(
+            eval_str(
+                &mut env,
+                "(symbol=? generated (string->symbol (symbol->string generated)))"
+            )
+            .unwrap(),
+            Value::Bool(false)
+        );
+    }
+
+    #[test]
+    fn call_cc_early_exit() {
+        let mut env = Env::new();
+        let code = "
+        (define find-first
+            (lambda (predicate l)
+                (call/cc (lambda (return)
+                    (let ((loop (lambda (rest)
+                            (cond ((= rest #nil) #f)
+                                  ((predicate (car rest)) (return (car rest)))
+                                  (#t (loop (cdr rest)))))))
+                        (loop l))))))
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(find-first (lambda (x) (> x 2)) (list 1 2 3 4))").unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            eval_str(&mut env, "(+ 1 (call/ec (lambda (k) (+ 10 (k 2)))))").unwrap(),
+            Value::Int(3)
+        );
+    }
+
+    #[test]
+    fn call_cc_reentry() {
+        let mut env = Env::new();
+        let code = "
+        (let ((count 0) (k #nil))
+            (begin
+                (define x (call/cc (lambda (c) (begin (set! k c) 0))))
+                (set! count (+ count 1))
+                (if (< x 3) (k (+ x 1)) count)))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(4));
+
+        eval_str(&mut env, "(define k #nil)").unwrap();
+        eval_str(
+            &mut env,
+            "(define r (+ 1 (call/cc (lambda (c) (begin (set! k c) 1)))))",
+        )
+        .unwrap();
+        assert_eq!(eval_str(&mut env, "(+ r 0)").unwrap(), Value::Int(2));
+        eval_str(&mut env, "(k 10)").unwrap();
+        assert_eq!(eval_str(&mut env, "(+ r 0)").unwrap(), Value::Int(11));
+        eval_str(&mut env, "(k 20)").unwrap();
+        assert_eq!(eval_str(&mut env, "(+ r 0)").unwrap(), Value::Int(21));
+    }
+
+    #[test]
+    fn escape_continuation_outside_its_extent() {
+        let mut env = Env::new();
+        eval_str(&mut env, "(define k (call/ec (lambda (c) c)))").unwrap();
+        assert!(eval_str(&mut env, "(k 1)").is_err());
+    }
+
+    /// Asserts that each code evaluates to what its expected code does.
+    fn assert_evaluates(env: &mut Env, cases: &[(impl AsRef<str>, &str)]) {
+        for (code, expected) in cases {
+            let code = code.as_ref();
+            assert_eq!(
+                eval_str(env, code).unwrap(),
+                eval_str(env, expected).unwrap(),
+                "{}",
+                code
+            );
+        }
+    }
+
+    fn env_with_log() -> Env {
+        let mut env = Env::new();
+        eval_str(&mut env, "(define log #nil)").unwrap();
+        eval_str(
+            &mut env,
+            "(define note (lambda (x) (set! log (cons x log))))",
+        )
+        .unwrap();
+        env
+    }
+
+    #[test]
+    fn dynamic_wind() {
+        let mut env = env_with_log();
+        let code = "
+        (dynamic-wind
+            (lambda () (note 1))
+            (lambda () (begin (note 2) 5))
+            (lambda () (note 3)))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(5));
+        assert_eq!(
+            eval_str(&mut env, "(= log (quote (3 2 1)))").unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn dynamic_wind_with_continuations() {
+        let mut env = env_with_log();
+        let code = "
+        (call/cc (lambda (k)
+            (dynamic-wind
+                (lambda () (note (quote in)))
+                (lambda () (k 1))
+                (lambda () (note (quote out))))))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(1));
+        assert_eq!(
+            eval_str(&mut env, "(= log (quote (out in)))").unwrap(),
+            Value::Bool(true)
+        );
+
+        let mut env = env_with_log();
+        let code = "
+        (let ((k #nil) (count 0))
+            (begin
+                (dynamic-wind
+                    (lambda () (note (quote in)))
+                    (lambda () (call/cc (lambda (c) (set! k c))))
+                    (lambda () (note (quote out))))
+                (set! count (+ count 1))
+                (if (< count 2) (k #nil) count)))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(2));
+        assert_eq!(
+            eval_str(&mut env, "(= log (quote (out in out in)))").unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn dynamic_wind_on_error() {
+        let mut env = env_with_log();
+        let code = "
+        (dynamic-wind
+            (lambda () (note 1))
+            (lambda () (car 1))
+            (lambda () (note 3)))
+        ";
+        assert!(eval_str(&mut env, code).is_err());
+        assert_eq!(
+            eval_str(&mut env, "(= log (quote (3 1)))").unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn frames_do_not_leak_on_error() {
+        let mut env = Env::new();
+        assert!(eval_str(&mut env, "(let ((a 1)) (car a))").is_err());
+        assert!(eval_str(&mut env, "(begin (define b 1) (car b))").is_err());
+        assert!(eval_str(&mut env, "(+ a 0)").is_err());
+        assert!(eval_str(&mut env, "(+ b 0)").is_err());
+        eval_str(&mut env, "(define c 1)").unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(let ((d c)) d)").unwrap(),
+            Value::Int(1)
+        );
+    }
+
+    #[test]
+    fn guard_catches_raised_values() {
+        let mut env = Env::new();
+        let code = "
+        (guard (e ((error-object? e) (error-object-message e)) (else e))
+            (+ 1 (raise 42)))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(42));
+        let code = "
+        (guard (e ((error-object? e) (error-object-irritants e)))
+            (error \"oops\" 1 2))
+        ";
         assert_eq!(
             eval_str(&mut env, code).unwrap(),
-            SymbolicExpression::Float(80.0)
+            eval_str(&mut env, "(quote (1 2))").unwrap()
+        );
+    }
+
+    #[test]
+    fn everything_but_false_is_true() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(
+            &mut env,
+            &[
+                ("(if 0 1 2)", "(quote 1)"),
+                ("(if (quote ()) 1 2)", "(quote 1)"),
+                ("(if #f 1 2)", "(quote 2)"),
+                ("(cond (\"\" 1) (#t 2))", "(quote 1)"),
+                ("(cond (#f 1) ((list) 2))", "(quote 2)"),
+                ("(or 1 2)", "(quote 1)"),
+                ("(or #f 2)", "(quote 2)"),
+                ("(and 1 2)", "(quote 2)"),
+                ("(and 1 #f 2)", "(quote #f)"),
+                ("(when 1 2)", "(quote 2)"),
+                ("(unless 1 2)", "(quote ())"),
+                ("(guard (e (1 2)) (raise 1))", "(quote 2)"),
+                ("(guard (e ((car (list e)))) (raise 3))", "(quote 3)"),
+            ],
+        );
+    }
+
+    #[test]
+    fn cond_clauses() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(
+            &mut env,
+            &[
+                ("(cond (#t 1 2))", "(quote 2)"),
+                ("(cond (#f 1) (else 2))", "(quote 2)"),
+                ("(cond (#f 1) (else 2 3))", "(quote 3)"),
+                ("(cond (1 2) (else 3))", "(quote 2)"),
+                ("(cond (#f 1) (else (and 2 3)))", "(quote 3)"),
+                (
+                    "(let ((x 1)) (cond ((= x 1) (set! x 2) (+ x 1)) (else x)))",
+                    "(quote 3)",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn guard_catches_interpreter_errors() {
+        let mut env = Env::new();
+        let code = "(guard (e ((error-object? e) #t)) (+ 1 undefined))";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Bool(true));
+        let code = "(guard (e ((error-object? e) #t)) (car 1))";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn guard_reraises_without_matching_clause() {
+        let mut env = env_with_log();
+        let code = "
+        (guard (outer (#t (note outer) outer))
+            (dynamic-wind
+                (lambda () (note 1))
+                (lambda () (guard (inner ((= inner 1) 0)) (raise 2)))
+                (lambda () (note 3))))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(2));
+        assert_eq!(
+            eval_str(&mut env, "(= log (quote (2 3 1)))").unwrap(),
+            Value::Bool(true)
         );
+    }
+
+    #[test]
+    fn with_exception_handler() {
+        let mut env = Env::new();
+        let code = "
+        (with-exception-handler
+            (lambda (condition) (* condition 2))
+            (lambda () (+ 1 (raise-continuable 20))))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(41));
+        let code = "
+        (with-exception-handler
+            (lambda (condition) 0)
+            (lambda () (raise 1)))
+        ";
+        assert!(eval_str(&mut env, code).is_err());
+    }
+
+    #[test]
+    fn uncaught_errors() {
+        let mut env = Env::new();
+        let error = eval_str(&mut env, "(error \"oops\" 1)").unwrap_err();
+        assert_eq!(error.to_string(), "Error: oops 1");
+        let error = eval_str(&mut env, "(raise 1)").unwrap_err();
+        assert_eq!(error.to_string(), "Error: uncaught exception 1");
+        assert!(matches!(
+            eval_str(&mut env, "(car 1)").unwrap_err(),
+            InterpreterError::ValueError(_)
+        ));
+    }
+
+    #[test]
+    fn syntax_rules() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        let code = "
+        (begin
+            (define-syntax swap!
+                (syntax-rules ()
+                    ((swap! a b) (let ((tmp a)) (begin (set! a b) (set! b tmp))))))
+            (define tmp 1)
+            (define other 2)
+            (swap! tmp other)
+            (list tmp other))
+        ";
         assert_eq!(
             eval_str(&mut env, code).unwrap(),
-            SymbolicExpression::Float(60.0)
+            eval_str(&mut env, "(list 2 1)").unwrap()
+        );
+        assert_eq!(
+            eval_str(&mut env, "(let ((value #f)) (or value 3))").unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            eval_str(&mut env, "(and (< 1 2) (< 2 3) (< 3 1))").unwrap(),
+            Value::Bool(false)
         );
     }
 
     #[test]
-    fn fib() {
-        let code =
-            "(define fib (lambda (n) (cond ((< n 2) 1) (#t (+ (fib (- n 1)) (fib (- n 2)))))))";
+    fn syntax_rules_ellipsis_and_literals() {
         let mut env = Env::new();
+        let code = "
+        (define-syntax my-cond
+            (syntax-rules (else)
+                ((my-cond (else result)) result)
+                ((my-cond (test result) clause ...) (if test result (my-cond clause ...)))))
+        ";
         eval_str(&mut env, code).unwrap();
         assert_eq!(
-            eval_str(&mut env, "(fib 0)").unwrap(),
-            SymbolicExpression::Int(1)
+            eval_str(&mut env, "(my-cond ((< 2 1) 1) ((< 3 1) 2) (else 3))").unwrap(),
+            Value::Int(3)
         );
+        assert!(eval_str(&mut env, "(let ((else #t)) (my-cond (else 3)))").is_err());
+        let code = "
+        (define-syntax my-let*
+            (syntax-rules ()
+                ((my-let* () body) body)
+                ((my-let* ((name value) rest ...) body) (let ((name value)) (my-let* (rest ...) body)))))
+        ";
+        eval_str(&mut env, code).unwrap();
         assert_eq!(
-            eval_str(&mut env, "(fib 1)").unwrap(),
-            SymbolicExpression::Int(1)
+            eval_str(&mut env, "(my-let* ((a 1) (b (+ a 1))) (list a b))").unwrap(),
+            eval_str(&mut env, "(list 1 2)").unwrap()
         );
+    }
+
+    #[test]
+    fn macros_keep_their_meaning_where_they_are_used() {
+        let mut env = Env::new();
+        let code = "
+        (define-syntax first
+            (syntax-rules () ((first pair) (car pair))))
+        ";
+        eval_str(&mut env, code).unwrap();
         assert_eq!(
-            eval_str(&mut env, "(fib 2)").unwrap(),
-            SymbolicExpression::Int(2)
+            eval_str(&mut env, "(let ((car cdr)) (first (list 1 2)))").unwrap(),
+            Value::Int(1)
         );
+        let code = "
+        (define-syntax my-if
+            (syntax-rules () ((my-if test then else) (cond (test then) (#t else)))))
+        ";
+        eval_str(&mut env, code).unwrap();
         assert_eq!(
-            eval_str(&mut env, "(fib 9)").unwrap(),
-            SymbolicExpression::Int(55)
+            eval_str(&mut env, "(let ((cond list)) (my-if #f 1 (cond 2)))").unwrap(),
+            eval_str(&mut env, "(list 2)").unwrap()
         );
     }
 
     #[test]
-    fn test_let() {
-        let code = "(let ((a 5) (b (+ 5 a))) (+ a b))";
+    fn local_macros() {
+        let mut env = Env::new();
+        let code = "
+        (let ((x 1))
+            (let-syntax ((get-x (syntax-rules () ((get-x) x))))
+                (let ((x 2)) (get-x))))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(1));
+        let code = "
+        (letrec-syntax
+            ((count (syntax-rules ()
+                ((count) 0)
+                ((count item rest ...) (+ 1 (count rest ...))))))
+            (count a b c))
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(3));
+        let code = "
+        (define-syntax define-getter
+            (syntax-rules ()
+                ((define-getter name value)
+                    (define-syntax name (syntax-rules () ((name) value))))))
+        ";
+        eval_str(&mut env, code).unwrap();
+        eval_str(&mut env, "(define-getter get-five 5)").unwrap();
+        assert_eq!(eval_str(&mut env, "(get-five)").unwrap(), Value::Int(5));
+    }
+
+    #[test]
+    fn macros_do_not_change_the_scope_of_let() {
         let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        eval_str(&mut env, "(define x 1)").unwrap();
+        for (plain, with_macro) in [
+            ("(let ((x (+ x 1))) x)", "(let ((x (+ x 1))) (and x))"),
+            (
+                "(let ((a 5) (b (+ 5 a))) (+ a b))",
+                "(let ((a 5) (b (+ 5 a))) (and (+ a b)))",
+            ),
+        ] {
+            assert_eq!(
+                eval_str(&mut env, plain).unwrap(),
+                eval_str(&mut env, with_macro).unwrap()
+            );
+        }
+        assert_eq!(
+            eval_str(&mut env, "(let ((x (+ x 1))) (and x))").unwrap(),
+            Value::Int(2)
+        );
+    }
+
+    #[test]
+    fn expanding_macros_reuses_symbols() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        let code = "
+        (define-syntax define-getter
+            (syntax-rules ()
+                ((define-getter name value)
+                    (define-syntax name (syntax-rules () ((name) (car (list value))))))))
+        ";
+        eval_str(&mut env, code).unwrap();
+        eval_str(&mut env, "(define-getter get-five 5)").unwrap();
+        let code = "(let ((x (get-five))) (let ((x (+ x 1))) (and x (or #f x))))";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(6));
+        let before = symbol::Symbol::gensym("g").index();
+        for _ in 0..100 {
+            assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(6));
+        }
+        assert_eq!(symbol::Symbol::gensym("g").index(), before + 1);
+    }
+
+    #[test]
+    fn explicit_renaming_macros() {
+        let mut env = Env::new();
+        let code = "
+        (module
+            (define nth (lambda (items index)
+                (if (= index 0) (car items) (nth (cdr items) (- index 1)))))
+            (define-syntax define-accessors
+                (er-macro-transformer
+                    (lambda (form rename compare)
+                        (let ((accessors (lambda (fields index)
+                                (if (= fields #nil)
+                                    #nil
+                                    (cons
+                                        (list (rename (quote define)) (car fields)
+                                            (list (rename (quote lambda)) (list (quote record))
+                                                (list (rename (quote nth)) (quote record) index)))
+                                        (accessors (cdr fields) (+ index 1)))))))
+                            (cons (rename (quote module)) (accessors (cdr form) 0)))))))
+        ";
+        eval_str(&mut env, code).unwrap();
+        eval_str(&mut env, "(define-accessors first second third)").unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(let ((nth car)) (third (list 1 2 3)))").unwrap(),
+            Value::Int(3)
+        );
+        let code = "
+        (define-syntax else?
+            (er-macro-transformer
+                (lambda (form rename compare) (compare (car (cdr form)) (rename (quote else))))))
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(else? else)").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval_str(&mut env, "(let ((else 1)) (else? else))").unwrap(),
+            Value::Bool(false)
+        );
+    }
+
+    #[test]
+    fn macroexpand() {
+        let mut env = Env::new();
+        let code = "
+        (module
+            (define-syntax add! (syntax-rules () ((add! x n) (set! x (+ x n)))))
+            (define-syntax inc! (syntax-rules () ((inc! x) (add! x 1)))))
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(macroexpand-1 (quote (inc! a)))").unwrap(),
+            eval_str(&mut env, "(quote (add! a 1))").unwrap()
+        );
+        assert_eq!(
+            eval_str(&mut env, "(macroexpand (quote (inc! a)))").unwrap(),
+            eval_str(&mut env, "(quote (set! a (+ a 1)))").unwrap()
+        );
+        assert_eq!(
+            eval_str(&mut env, "(macroexpand (quote (+ 1 2)))").unwrap(),
+            eval_str(&mut env, "(quote (+ 1 2))").unwrap()
+        );
+    }
+
+    #[test]
+    fn bodies_and_internal_defines() {
+        let mut env = Env::new();
+        let code = "
+        (define (parity n)
+            (define (even? n) (if (= n 0) #t (odd? (- n 1))))
+            (define (odd? n) (if (= n 0) #f (even? (- n 1))))
+            (if (even? n) (quote even) (quote odd)))
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(parity 7)").unwrap(),
+            Value::Symbol(Symbol::intern("odd"))
+        );
+        let code = "
+        (let ((counter 0))
+            (set! counter (+ counter 1))
+            (set! counter (+ counter 1))
+            counter)
+        ";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(2));
+        eval_str(&mut env, "(define (tail first . rest) rest)").unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(tail 1 2 3)").unwrap(),
+            eval_str(&mut env, "(list 2 3)").unwrap()
+        );
+        assert_eq!(eval_str(&mut env, "(tail 1)").unwrap(), Value::Nil);
+    }
+
+    #[test]
+    fn internal_defines_shadow_from_the_start() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        eval_str(&mut env, "(define x 1)").unwrap();
+        let code = "
+        (define (shadowed)
+            (define y x)
+            (define x 2)
+            y)
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert!(eval_str(&mut env, "(shadowed)").is_err());
+        let code = "
+        (define (shadowed-with-macros)
+            (define y (or x 0))
+            (define x 2)
+            y)
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert!(eval_str(&mut env, "(shadowed-with-macros)").is_err());
+    }
+
+    #[test]
+    fn variadic_procedures() {
+        let mut env = Env::new();
+        eval_str(&mut env, "(define all (lambda args args))").unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(all 1 2)").unwrap(),
+            eval_str(&mut env, "(list 1 2)").unwrap()
+        );
+        let code = "
+        (define area
+            (case-lambda
+                ((side) (* side side))
+                ((width height) (* width height))
+                ((width height . more) (quote too-many))))
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert_eq!(eval_str(&mut env, "(area 3)").unwrap(), Value::Int(9));
+        assert_eq!(eval_str(&mut env, "(area 2 3)").unwrap(), Value::Int(6));
+        assert_eq!(
+            eval_str(&mut env, "(area 2 3 4)").unwrap(),
+            Value::Symbol(Symbol::intern("too-many"))
+        );
+        assert!(eval_str(&mut env, "(area)").is_err());
+    }
+
+    #[test]
+    fn optional_and_keyword_parameters() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        let code = "
+        (define (plot data #!optional (title (quote untitled)) #!key (width 10) (height (* width 2)))
+            (list data title width height))
+        ";
+        eval_str(&mut env, code).unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(plot 1)").unwrap(),
+            eval_str(&mut env, "(quote (1 untitled 10 20))").unwrap()
+        );
+        assert_eq!(
+            eval_str(&mut env, "(plot 1 (quote sales) height: 5)").unwrap(),
+            eval_str(&mut env, "(quote (1 sales 10 5))").unwrap()
+        );
+        assert_eq!(
+            eval_str(&mut env, "(when #t (plot 1 (quote sales) width: 4))").unwrap(),
+            eval_str(&mut env, "(quote (1 sales 4 8))").unwrap()
+        );
+        assert!(eval_str(&mut env, "(plot 1 (quote sales) depth: 5)").is_err());
+        assert!(eval_str(&mut env, "(plot 1 (quote sales) width:)").is_err());
+    }
+
+    #[test]
+    fn arity_errors() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        eval_str(&mut env, "(define (add a b) (+ a b))").unwrap();
+        eval_str(&mut env, "(define sum (lambda (a b . rest) a))").unwrap();
+        let message = |env: &mut Env, code| match eval_str(env, code) {
+            Err(InterpreterError::ArgumentError(message)) => message,
+            other => panic!("expected an argument error, got {:?}", other),
+        };
+        assert_eq!(
+            message(&mut env, "(add 1)"),
+            "add expects 2 arguments, got 1"
+        );
+        assert_eq!(
+            message(&mut env, "(add 1 2 3)"),
+            "add expects 2 arguments, got 3"
+        );
+        assert_eq!(
+            message(&mut env, "(sum 1)"),
+            "sum expects at least 2 arguments, got 1"
+        );
+        assert_eq!(
+            message(&mut env, "(car 1 2)"),
+            "car expects 1 argument, got 2"
+        );
+        assert_eq!(
+            message(&mut env, "(cons 1)"),
+            "cons expects 2 arguments, got 1"
+        );
+        assert_eq!(
+            message(&mut env, "(gensym 1 2)"),
+            "gensym expects between 0 and 1 arguments, got 2"
+        );
+        assert_eq!(
+            message(&mut env, "((lambda (x) x))"),
+            "lambda expects 1 argument, got 0"
+        );
+        assert_eq!(eval_str(&mut env, "(sum 1 2 3)").unwrap(), Value::Int(1));
+    }
+
+    #[test]
+    fn builtins_can_be_shadowed() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        eval_str(&mut env, "(define (first list) (car list))").unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(first (list 1 2))").unwrap(),
+            Value::Int(1)
+        );
+        assert_eq!(
+            eval_str(&mut env, "(let ((if list)) (if 1 2))").unwrap(),
+            eval_str(&mut env, "(quote (1 2))").unwrap()
+        );
+        assert_eq!(
+            eval_str(&mut env, "(when #t (let ((if +)) (if 1 2)))").unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            eval_str(&mut env, "(map car (quote ((1) (2))))").unwrap(),
+            eval_str(&mut env, "(quote (1 2))").unwrap()
+        );
+        assert_eq!(
+            eval_str(&mut env, "(car (quote (car)))").unwrap(),
+            Value::Symbol(Symbol::intern("car"))
+        );
+        assert_eq!(eval_str(&mut env, "(if #t 1 2)").unwrap(), Value::Int(1));
+        let mut other = Env::new();
+        eval_str(&mut other, "(define car cdr)").unwrap();
+        assert_eq!(
+            eval_str(&mut other, "(car (list 1 2))").unwrap(),
+            eval_str(&mut other, "(list 2)").unwrap()
+        );
+        assert_eq!(
+            eval_str(&mut env, "(car (list 1 2))").unwrap(),
+            Value::Int(1)
+        );
+    }
+
+    #[test]
+    fn higher_order_procedures() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(
+            &mut env,
+            &[
+                ("(apply + 1 2 (list 3 4))", "(quote 10)"),
+                ("(apply list (quote ()))", "(quote ())"),
+                ("(map + (list 1 2 3) (list 10 20))", "(list 11 22)"),
+                ("(map (lambda (x) (* x x)) (list 1 2 3))", "(list 1 4 9)"),
+                (
+                    "(vector-map - (vector 1 2) (vector 3 4 5))",
+                    "(vector -2 -2)",
+                ),
+                ("(string-map char-upcase \"abc\")", "(quote \"ABC\")"),
+                (
+                    "(fold-left list (quote ()) (list 1 2 3))",
+                    "(quote (((() 1) 2) 3))",
+                ),
+                (
+                    "(fold-right list (quote ()) (list 1 2 3))",
+                    "(quote (1 (2 (3 ()))))",
+                ),
+                ("(fold-left + 0 (list 1 2) (list 10 20))", "(quote 33)"),
+                ("(reduce - 0 (list 1 2 3))", "(quote 2)"),
+                ("(reduce-recursive - (list 1 2 3))", "(quote 2)"),
+                (
+                    "(reduce + 0 (map (lambda (x) (* x x)) (range 100)))",
+                    "(reduce-recursive + (map-recursive (lambda (x) (* x x)) (range 100)))",
+                ),
+                ("(reduce + 0 (quote ()))", "(quote 0)"),
+                (
+                    "(vector->list (list->vector (list 1 #\\a)))",
+                    "(list 1 #\\a)",
+                ),
+            ],
+        );
+        eval_str(&mut env, "(define total 0)").unwrap();
+        let code = "(for-each (lambda (x y) (set! total (+ total (* x y)))) (list 1 2) (list 3 4))";
+        eval_str(&mut env, code).unwrap();
+        assert_eq!(eval_str(&mut env, "(+ total)").unwrap(), Value::Int(11));
+        let code = "(call/cc (lambda (k) (map (lambda (x) (if (= x 2) (k x) x)) (list 1 2 3))))";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(2));
+        assert!(eval_str(&mut env, "(map car (list 1))").is_err());
+        assert!(eval_str(&mut env, "(apply + 1 2)").is_err());
+    }
+
+    #[test]
+    fn list_library() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        eval_str(&mut env, "(define xs (list 1 2 3 4 5))").unwrap();
+        eval_str(&mut env, "(define dotted (cons 1 (cons 2 3)))").unwrap();
+        eval_str(
+            &mut env,
+            "(define table (list (cons 1 (quote one)) (cons 2 (quote two))))",
+        )
+        .unwrap();
+        assert_evaluates(
+            &mut env,
+            &[
+                (
+                    "(append (list 1) (list 2 3) 4)",
+                    "(cons 1 (cons 2 (cons 3 4)))",
+                ),
+                ("(append)", "(quote ())"),
+                ("(reverse xs)", "(list 5 4 3 2 1)"),
+                ("(length xs)", "(quote 5)"),
+                ("(list-tail xs 3)", "(list 4 5)"),
+                ("(list-ref xs 1)", "(quote 2)"),
+                ("(list-copy dotted)", "(cons 1 (cons 2 3))"),
+                ("(last-pair dotted)", "(cons 2 3)"),
+                ("(filter (lambda (x) (< x 3)) xs)", "(list 1 2)"),
+                ("(remove (lambda (x) (< x 3)) xs)", "(list 3 4 5)"),
+                (
+                    "(partition (lambda (x) (< x 2)) xs)",
+                    "(list (list 1) (list 2 3 4 5))",
+                ),
+                ("(delete 3 xs)", "(list 1 2 4 5)"),
+                ("(delete 3 xs <)", "(list 1 2 3)"),
+                ("(delete-duplicates (list 1 2 1 3 2))", "(list 1 2 3)"),
+                (
+                    "(delete-duplicates (list 1 2 4 3) (lambda (a b) (= (- b a) 1)))",
+                    "(list 1 4 3)",
+                ),
+                ("(find (lambda (x) (> x 3)) xs)", "(quote 4)"),
+                ("(find-tail (lambda (x) (> x 3)) xs)", "(list 4 5)"),
+                ("(find (lambda (x) (> x 9)) xs)", "(quote #f)"),
+                (
+                    "(any (lambda (x y) (and (> x 2) (+ x y))) xs xs)",
+                    "(quote 6)",
+                ),
+                ("(every (lambda (x) (and (> x 0) x)) xs)", "(quote 5)"),
+                ("(every (lambda (x) (> x 1)) xs)", "(quote #f)"),
+                ("(count (lambda (x) (> x 1)) xs)", "(quote 4)"),
+                ("(iota 3)", "(list 0 1 2)"),
+                ("(iota 3 1 2)", "(list 1 3 5)"),
+                ("(take dotted 2)", "(list 1 2)"),
+                ("(drop dotted 2)", "(quote 3)"),
+                ("(assoc 2 table)", "(cons 2 (quote two))"),
+                ("(assq 3 table)", "(quote #f)"),
+                (
+                    "(assoc 2 table (lambda (a b) (= a (+ b 1))))",
+                    "(cons 1 (quote one))",
+                ),
+                ("(member 3 xs)", "(list 3 4 5)"),
+                ("(memq 9 xs)", "(quote #f)"),
+                ("(member 3 xs <)", "(list 4 5)"),
+                ("(memv 3 xs)", "(list 3 4 5)"),
+                ("(memq (list 1) (list (list 1)))", "(quote #f)"),
+                ("(member (list 1) (list (list 1)))", "(list (list 1))"),
+                ("(memq \"a\" (list \"a\"))", "(quote #f)"),
+                (
+                    "(let ((x (list 1))) (memq x (list (list 1) x)))",
+                    "(list (list 1))",
+                ),
+                ("(assv 2 table)", "(cons 2 (quote two))"),
+                ("(assq (list 1) (list (cons (list 1) 2)))", "(quote #f)"),
+                (
+                    "(assoc (list 1) (list (cons (list 1) 2)))",
+                    "(cons (list 1) 2)",
+                ),
+                (
+                    "(let ((key (vector 1))) (assq key (list (cons (vector 1) 1) (cons key 2))))",
+                    "(cons (vector 1) 2)",
+                ),
+                (
+                    "(append-map (lambda (x) (list x x)) (list 1 2))",
+                    "(list 1 1 2 2)",
+                ),
+                (
+                    "(filter-map (lambda (x) (and (> x 3) (* x x))) xs)",
+                    "(list 16 25)",
+                ),
+            ],
+        );
+        for code in [
+            "(length dotted)",
+            "(filter (lambda (x) #t) dotted)",
+            "(list-ref xs 5)",
+            "(take xs 6)",
+            "(assq 1 (list 1))",
+        ] {
+            assert!(eval_str(&mut env, code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn sorting() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        eval_str(&mut env, "(define (car<? a b) (< (car a) (car b)))").unwrap();
+        eval_str(
+            &mut env,
+            "(define pairs (list (cons 2 1) (cons 1 2) (cons 2 3) (cons 1 4)))",
+        )
+        .unwrap();
+        assert_evaluates(
+            &mut env,
+            &[
+                ("(list-sort < (list 3 1 2 5 4))", "(list 1 2 3 4 5)"),
+                ("(list-sort < (quote ()))", "(quote ())"),
+                (
+                    "(list-sort car<? pairs)",
+                    "(list (cons 1 2) (cons 1 4) (cons 2 1) (cons 2 3))",
+                ),
+                ("(vector-sort > (vector 1 3 2))", "(vector 3 2 1)"),
+                ("(vector-sort < (vector 4 3 2 1) 1 3)", "(vector 2 3)"),
+                (
+                    "(list-merge car<? (list (cons 1 1) (cons 3 1)) (list (cons 1 2) (cons 2 2)))",
+                    "(list (cons 1 1) (cons 1 2) (cons 2 2) (cons 3 1))",
+                ),
+                ("(list-merge < (quote ()) (list 1 2))", "(list 1 2)"),
+                ("(sorted? (list 1 2 2 3) <)", "(quote #t)"),
+                ("(sorted? (vector 1 3 2) <)", "(quote #f)"),
+            ],
+        );
+        eval_str(&mut env, "(define v (vector 5 4 3 2 1))").unwrap();
+        eval_str(&mut env, "(vector-sort! v < 1)").unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(vector->list v)").unwrap(),
+            eval_str(&mut env, "(list 5 1 2 3 4)").unwrap()
+        );
+        let code = "(vector-sort! v (lambda (a b) (error \"unordered\" a b)))";
+        assert!(eval_str(&mut env, code).is_err());
+        assert_eq!(
+            eval_str(&mut env, "(vector->list v)").unwrap(),
+            eval_str(&mut env, "(list 5 1 2 3 4)").unwrap()
+        );
+        let code = "(guard (e (#t (error-object-message e))) (list-sort (lambda (a b) (error \"unordered\")) (list 1 2)))";
         assert_eq!(
             eval_str(&mut env, code).unwrap(),
-            SymbolicExpression::Int(15)
+            Value::string("unordered")
+        );
+        let code = "(length (list-sort > (iota 10000)))";
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(10000));
+    }
+
+    #[test]
+    fn string_library() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(
+            &mut env,
+            &[
+                ("(string-length \"héllo\")", "(quote 5)"),
+                ("(string-ref \"héllo\" 1)", "(quote #\\é)"),
+                ("(substring \"héllo\" 1 3)", "(quote \"él\")"),
+                ("(string-copy \"héllo\" 2)", "(quote \"llo\")"),
+                ("(string-append \"a\" \"bc\" \"\")", "(quote \"abc\")"),
+                ("(string->list \"ab\")", "(list #\\a #\\b)"),
+                (
+                    "(list->string (list #\\a #\\space))",
+                    "(string #\\a #\\space)",
+                ),
+                ("(string-upcase \"straße\")", "(quote \"STRASSE\")"),
+                ("(string-downcase \"ÀB\")", "(quote \"àb\")"),
+                ("(string-index \"héllo\" #\\l)", "(quote 2)"),
+                (
+                    "(string-index \"héllo\" (lambda (c) (string=? (string c) \"o\")))",
+                    "(quote 4)",
+                ),
+                ("(string-index \"abc\" #\\z)", "(quote #f)"),
+                ("(string-contains \"héllo\" \"lo\")", "(quote 3)"),
+                (
+                    "(string-split \"a,b,,c\" #\\,)",
+                    "(list \"a\" \"b\" \"\" \"c\")",
+                ),
+                (
+                    "(string-split (string #\\a #\\space #\\b))",
+                    "(list \"a\" \"b\")",
+                ),
+                ("(string-join (list \"a\" \"b\") \"-\")", "(quote \"a-b\")"),
+                (
+                    "(string-trim-both (string #\\space #\\a #\\tab))",
+                    "(quote \"a\")",
+                ),
+                (
+                    "(string-trim (string #\\space #\\a #\\space))",
+                    "(string #\\a #\\space)",
+                ),
+                ("(string=? \"a\" \"a\" \"a\")", "(quote #t)"),
+                ("(string<? \"a\" \"b\" \"c\")", "(quote #t)"),
+                ("(string>=? \"a\" \"b\")", "(quote #f)"),
+                ("(make-string 3 #\\x)", "(quote \"xxx\")"),
+            ],
+        );
+        eval_str(&mut env, "(define s (string-copy \"héllo\"))").unwrap();
+        eval_str(&mut env, "(define t s)").unwrap();
+        eval_str(&mut env, "(string-set! s 1 #\\e)").unwrap();
+        eval_str(&mut env, "(string-fill! s #\\ü 3)").unwrap();
+        assert_eq!(
+            eval_str(&mut env, "(string-append t)").unwrap(),
+            Value::string("helüü")
+        );
+        assert!(eval_str(&mut env, "(string-ref \"abc\" 3)").is_err());
+        assert!(eval_str(&mut env, "(substring \"abc\" 2 1)").is_err());
+        assert!(eval_str(&mut env, "(string-set! s 5 #\\a)").is_err());
+    }
+
+    #[test]
+    fn regular_expressions() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        eval_str(&mut env, "(define date (regexp \"(\\d+)-(\\d+)(x)?\"))").unwrap();
+        assert_evaluates(
+            &mut env,
+            &[
+                ("(regexp? date)", "(quote #t)"),
+                ("(regexp? \"a\")", "(quote #f)"),
+                (
+                    "(regexp-match date \"on:2024-05!\")",
+                    "(list \"2024-05\" \"2024\" \"05\" #f)",
+                ),
+                ("(regexp-match \"b+\" \"abba\")", "(list \"bb\")"),
+                ("(regexp-match \"z\" \"abba\")", "(quote #f)"),
+                (
+                    "(regexp-match-positions \"b+\" \"éabba\")",
+                    "(list (cons 2 4))",
+                ),
+                (
+                    "(regexp-replace \"(a)(b)\" \"abab\" \"$2$1\")",
+                    "(quote \"baab\")",
+                ),
+                (
+                    "(regexp-replace-all \"(a)(b)\" \"abab\" \"$2$1\")",
+                    "(quote \"baba\")",
+                ),
+                (
+                    "(regexp-split \",+\" \"a,,b,c\")",
+                    "(list \"a\" \"b\" \"c\")",
+                ),
+                (
+                    "(regexp-split \" +\" \"a  (b) \\\"c\\\"\")",
+                    "(list \"a\" \"(b)\" \"\\\"c\\\"\")",
+                ),
+                (
+                    "(list->vector (cdr (regexp-match date \"1-2\")))",
+                    "(vector \"1\" \"2\" #f)",
+                ),
+            ],
+        );
+        assert!(eval_str(&mut env, "(regexp \"(\")").is_err());
+        assert!(eval_str(&mut env, "(regexp-match 1 \"a\")").is_err());
+    }
+
+    #[test]
+    fn string_ports() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(&mut env, &[
+            (
+                "(call-with-output-string (lambda (port) (display \"a\" port) (write \"b\\n\" port)))",
+                "(quote \"a\\\"b\\\\n\\\"\")",
+            ),
+            (
+                "(call-with-output-string (lambda (port) (write #\\a port) (newline port) (display #\\a port)))",
+                "(string #\\# #\\\\ #\\a #\\newline #\\a)",
+            ),
+            (
+                "(with-output-to-string (lambda () (display (list 1 \"x\" #\\y (vector 2 (quote z))))))",
+                "(quote \"(1 x y #(2 z))\")",
+            ),
+            (
+                "(with-output-to-string (lambda () (write (cons 1 (cons 2 3))) (write (list))))",
+                "(quote \"(1 2 . 3)()\")",
+            ),
+            (
+                "(with-output-to-string (lambda () (call/cc (lambda (k) (with-output-to-string (lambda () (k 0))))) (display 1)))",
+                "(quote \"1\")",
+            ),
+            (
+                "(let ((port (open-output-string))) (write (quote sym) port) (get-output-string port))",
+                "(quote \"sym\")",
+            ),
+            (
+                "(let ((port (open-input-string \"ab\\ncd\"))) (list (read-char port) (peek-char port) (read-line port) (read-line port) (eof-object? (read-char port))))",
+                "(list #\\a #\\b \"b\" \"cd\" #t)",
+            ),
+            (
+                "(string-length (read-string 1000001 (open-input-string (make-string 1000000 #\\a))))",
+                "(quote 1000000)",
+            ),
+            ("(eof-object? (eof-object))", "(quote #t)"),
+        ]);
+        assert!(eval_str(&mut env, "(get-output-string (open-input-string \"a\"))").is_err());
+        assert!(eval_str(&mut env, "(display 1 (open-input-string \"a\"))").is_err());
+        assert!(eval_str(&mut env, "(with-output-to-string (lambda () (car 1)))").is_err());
+        assert_eq!(
+            eval_str(&mut env, "(with-output-to-string (lambda () (display 2)))").unwrap(),
+            Value::string("2")
         );
     }
+
+    #[test]
+    fn output_procedures() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(&mut env, &[
+            (
+                "(with-output-to-string (lambda () (write-char #\\a) (write-string \"bcd\" (current-output-port) 1 2) (write-simple \"e\")))",
+                "(quote \"ac\\\"e\\\"\")",
+            ),
+            (
+                "(let ((v (vector 1))) (with-output-to-string (lambda () (write-shared (list v v \"s\")))))",
+                "(quote \"(#0=#(1) #0# \\\"s\\\")\")",
+            ),
+            (
+                "(let ((xs (list 1 2))) (with-output-to-string (lambda () (write-shared (list xs (cdr xs))))))",
+                "(quote \"((1 . #0=(2)) #0#)\")",
+            ),
+            (
+                "(let ((xs (list 1 2))) (with-output-to-string (lambda () (write (list xs xs)))))",
+                "(quote \"((1 2) (1 2))\")",
+            ),
+            (
+                "(let ((port (open-output-string))) (with-output-to-port port (lambda () (display 1) (flush-output-port))) (display 2 port) (get-output-string port))",
+                "(quote \"12\")",
+            ),
+        ]);
+        assert!(eval_str(&mut env, "(write-char \"a\")").is_err());
+        assert!(eval_str(&mut env, "(write-string \"abc\" (current-output-port) 2 5)").is_err());
+        assert!(eval_str(&mut env, "(with-output-to-port 1 (lambda () 1))").is_err());
+    }
+
+    #[test]
+    fn file_ports() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        let path = std::env::temp_dir().join(format!("file-ports-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_evaluates(&mut env, &[
+            (
+                format!(
+                    "(with-output-to-file \"{}\" (lambda () (display \"héllo\") (newline) (write \"x\")))",
+                    path
+                ),
+                "(quote #nil)",
+            ),
+            (
+                format!(
+                    "(call-with-input-file \"{}\" (lambda (port) (list (read-line port) (read-char port) (peek-char port) (read-string 5 port) (eof-object? (read-line port)))))",
+                    path
+                ),
+                "(list \"héllo\" #\\\" #\\x \"x\\\"\" #t)",
+            ),
+            (
+                format!(
+                    "(let ((port (open-output-file \"{}\"))) (write-string \"a\\nb\" port) (close-port port) (close-port port))",
+                    path
+                ),
+                "(quote #nil)",
+            ),
+            (
+                format!(
+                    "(let ((port (open-input-file \"{}\"))) (list (char-ready? port) (read-string 3 port) (eof-object? (read-char port)) (begin (close-port port) (guard (e (#t (error-object-message e))) (read-char port)))))",
+                    path
+                ),
+                "(list #t \"a\\nb\" #t \"cannot read from a closed port\")",
+            ),
+            (
+                "(guard (e ((error-object? e) 1)) (open-input-file \"/nonexistent/file\"))".into(),
+                "(quote 1)",
+            ),
+        ]);
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(
+            eval_file(&mut env, "/nonexistent/file.scm"),
+            Err(InterpreterError::IoError(_))
+        ));
+        assert!(eval_str(&mut env, "(call-with-input-file 1 car)").is_err());
+    }
+
+    #[test]
+    fn bytevectors() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(&mut env, &[
+            ("(bytevector-length #u8(1 2 255))", "(quote 3)"),
+            ("(bytevector 1 2)", "(quote #u8(1 2))"),
+            ("(make-bytevector 2 7)", "(bytevector 7 7)"),
+            (
+                "(let ((bytes (make-bytevector 2))) (bytevector-u8-set! bytes 1 9) (list (bytevector-u8-ref bytes 1) (bytevector? bytes) (bytevector? 1)))",
+                "(list 9 #t #f)",
+            ),
+            ("(bytevector-copy #u8(1 2 3 4) 1 3)", "(quote #u8(2 3))"),
+            ("(bytevector-append #u8(1) #u8() #u8(2 3))", "(quote #u8(1 2 3))"),
+            ("(string->utf8 \"aé\")", "(quote #u8(97 195 169))"),
+            ("(utf8->string #u8(97 195 169 98) 1)", "(quote \"éb\")"),
+            (
+                "(list (bytevector-u16-ref #u8(1 2) 0 (quote big)) (bytevector-u16-ref #u8(1 2) 0 (quote little)))",
+                "(list 258 513)",
+            ),
+            (
+                "(list (bytevector-s16-ref #u8(255 254) 0 (quote big)) (bytevector-u32-ref #u8(0 0 0 0 1) 1 (quote little)))",
+                "(list -2 16777216)",
+            ),
+            (
+                "(let ((bytes (make-bytevector 8))) (bytevector-s64-set! bytes 0 -2 (quote little)) (list (bytevector-u8-ref bytes 0) (bytevector-u8-ref bytes 7) (bytevector-s64-ref bytes 0 (quote little))))",
+                "(list 254 255 -2)",
+            ),
+            (
+                "(let ((bytes (make-bytevector 4))) (bytevector-u32-set! bytes 0 4294967295 (quote big)) (bytevector-s32-ref bytes 0 (quote big)))",
+                "(quote -1)",
+            ),
+            (
+                "(let ((port (open-input-bytevector #u8(1 2 3)))) (list (peek-u8 port) (read-u8 port) (u8-ready? port) (read-bytevector 5 port) (eof-object? (read-u8 port)) (eof-object? (read-bytevector 1 port))))",
+                "(list 1 1 #t #u8(2 3) #t #t)",
+            ),
+            (
+                "(let ((port (open-output-bytevector))) (write-u8 1 port) (write-bytevector #u8(2 3 4) port 1) (get-output-bytevector port))",
+                "(quote #u8(1 3 4))",
+            ),
+        ]);
+        for code in [
+            "(bytevector 256)",
+            "(list #u8(1 x))",
+            "(list #u8(256))",
+            "(bytevector-u8-ref #u8(1) 1)",
+            "(bytevector-u16-ref #u8(1) 0 (quote big))",
+            "(bytevector-u16-ref #u8(1 2) 0 (quote middle))",
+            "(bytevector-u16-set! (make-bytevector 2) 0 65536 (quote big))",
+            "(bytevector-s16-set! (make-bytevector 2) 0 -32769 (quote big))",
+            "(utf8->string #u8(255))",
+            "(write-u8 1 (open-output-string))",
+            "(read-char (open-input-bytevector #u8(97)))",
+            "(read-u8 (open-input-string \"a\"))",
+        ] {
+            assert!(
+                eval_str(&mut env, code).is_err(),
+                "{} should be an error",
+                code
+            );
+        }
+        for index in [i64::MAX as u64, u64::MAX] {
+            for code in [
+                format!("(bytevector-u8-ref #u8(1 2) {})", index),
+                format!("(bytevector-u8-set! #u8(1 2) {} 0)", index),
+                format!("(bytevector-u16-ref #u8(1 2) {} (quote big))", index),
+                format!("(bytevector-s64-set! #u8(1 2) {} 0 (quote little))", index),
+            ] {
+                assert!(
+                    matches!(
+                        eval_str(&mut env, &code),
+                        Err(InterpreterError::ValueError(_))
+                    ),
+                    "{} should be a ValueError",
+                    code
+                );
+            }
+        }
+        let path = std::env::temp_dir().join(format!("bytevectors-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let code = format!(
+            "(let ((port (open-binary-output-file \"{}\"))) (write-bytevector #u8(0 1 2) port) (close-port port) (let ((port (open-binary-input-file \"{}\"))) (read-bytevector 10 port)))",
+            path, path
+        );
+        assert_eq!(
+            eval_str(&mut env, &code).unwrap(),
+            eval_str(&mut env, "(quote #u8(0 1 2))").unwrap()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reading_data() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(&mut env, &[
+            (
+                "(let ((port (open-input-string \" (a \\\"b) c\\\" (1 2.5)) 42\\n#\\\\( #u8(1 2)sym\"))) (list (read port) (read port) (read port) (read port) (read port) (eof-object? (read port))))",
+                "(list (list (quote a) \"b) c\" (list 1 2.5)) 42 #\\( #u8(1 2) (quote sym) #t)",
+            ),
+            (
+                "(let ((port (open-input-string \"x\"))) (list (read port) (eof-object? (read port))))",
+                "(list (quote x) #t)",
+            ),
+            (
+                "(guard (e ((read-error? e) (error-object-message e))) (read (open-input-string \"(a (b)\")))",
+                "(quote \"unexpected end of input\")",
+            ),
+            (
+                "(guard (e ((read-error? e) (error-object-message e))) (read (open-input-string \")\")))",
+                "(quote \"unexpected )\")",
+            ),
+            (
+                "(guard (e ((file-error? e) 1)) (open-input-file \"/nonexistent/file\"))",
+                "(quote 1)",
+            ),
+            ("(list (read-error? 1) (file-error? 1))", "(list #f #f)"),
+        ]);
+        assert!(matches!(
+            eval_str(&mut env, "(read (open-input-string \"\\\"abc\"))"),
+            Err(InterpreterError::ReadError(_))
+        ));
+    }
+
+    #[test]
+    fn environments() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(&mut env, &[
+            (
+                "(eval (list (quote +) 1 2) (scheme-report-environment 5))",
+                "(quote 3)",
+            ),
+            (
+                "(let ((x 1)) (eval (quote (fact 3)) (interaction-environment)))",
+                "(quote 6)",
+            ),
+            (
+                "(eval (quote (if #t 1 2)) (null-environment 5))",
+                "(quote 1)",
+            ),
+            (
+                "(let ((rules (restricted-environment (quote (+ > and if)) (interaction-environment)))) (eval (quote (define limit 10)) rules) (eval (quote (and (> (+ limit 1) 10) 2)) rules))",
+                "(quote 2)",
+            ),
+            (
+                "(let ((rules (restricted-environment (quote (*))))) (eval (quote (define limit 10)) rules) (environment? rules))",
+                "(quote #t)",
+            ),
+            (
+                "(let ((rules (restricted-environment (quote (list eval interaction-environment))))) (eval (quote (eval (quote (list 1)) (interaction-environment))) rules))",
+                "(list 1)",
+            ),
+            ("(begin (define e eval) (e (quote (+ 1 2))))", "(quote 3)"),
+            (
+                "(map eval (list (quote (+ 1 2)) (quote (fact 3))))",
+                "(list 3 6)",
+            ),
+            ("(let ((fact car)) (eval (quote (fact 3))))", "(quote 6)"),
+            (
+                "(environment? (apply interaction-environment (list)))",
+                "(quote #t)",
+            ),
+        ]);
+        for code in [
+            "(eval (quote (car (list 1))) (null-environment 5))",
+            "(eval (quote (fact 3)) (scheme-report-environment 5))",
+            "(eval (quote (fact 3)) (restricted-environment (quote (car))))",
+            "(eval (quote (open-output-file \"f\")) (restricted-environment (quote (car))))",
+            "(eval (quote limit) (interaction-environment))",
+            "(let ((local 1)) (eval (quote local)))",
+            "(eval (quote (eval 1)) (restricted-environment (quote (list))))",
+            "(restricted-environment (quote (no-such-binding)))",
+            "(eval 1 2)",
+            "(scheme-report-environment 6)",
+        ] {
+            assert!(
+                eval_str(&mut env, code).is_err(),
+                "{} should be an error",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn format_templates() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        assert_evaluates(&mut env, &[
+            (
+                "(format \"~a and ~s~%\" \"x\" \"y\")",
+                "(quote \"x and \\\"y\\\"\\n\")",
+            ),
+            (
+                "(format #f \"~d ~x ~o ~b ~~\" -42 255 8 5)",
+                "(quote \"-42 ff 10 101 ~\")",
+            ),
+            ("(format \"[~5d|~5a]\" 42 (quote ab))", "(quote \"[   42|ab   ]\")"),
+            (
+                "(format \"~8,3F|~,1f|~f|~6F\" 3.14159 2 0.5 \"ab\")",
+                "(quote \"   3.142|2.0|0.5|    ab\")",
+            ),
+            (
+                "(format \"~c~_~? ~w\" #\\a \"<~a>\" (list 1) (list 1 2))",
+                "(quote \"a <1> (1 2)\")",
+            ),
+            ("(format \"a~&b~&~%\")", "(quote \"a\\nb\\n\\n\")"),
+            (
+                "(with-output-to-string (lambda () (format #t \"~a-~a\" 1 2)))",
+                "(quote \"1-2\")",
+            ),
+            (
+                "(let ((port (open-output-string))) (format port \"~S\" #\\b) (get-output-string port))",
+                "(quote \"#\\\\b\")",
+            ),
+        ]);
+        for code in [
+            "(format \"~a ~a\" 1)",
+            "(format \"~a\" 1 2)",
+            "(format \"~d\" 1.5)",
+            "(format \"~c\" \"a\")",
+            "(format \"~q\" 1)",
+            "(format \"~\")",
+            "(format 1 \"a\")",
+            "(format #f 1)",
+            "(format \"~1000000000000000000a\" 1)",
+            "(format \"~1,100000000000000F\" 1.5)",
+        ] {
+            assert!(
+                matches!(
+                    eval_str(&mut env, code),
+                    Err(InterpreterError::ArgumentError(_) | InterpreterError::ValueError(_))
+                ),
+                "{} should be an argument error",
+                code
+            );
+        }
+    }
+
+    /// Tokens random programs are made of: every primitive and special
+    /// form, some of std.scm, and the pieces of lambda lists and macros.
+    const FUZZ_VOCABULARY: [&str; 31] = [
+        "0",
+        "1",
+        "-1",
+        "-2",
+        "2.5",
+        // i64::MAX, u64::MAX and i128::MAX, as counts and indices
+        "9223372036854775807",
+        "18446744073709551615",
+        "170141183460469231731687303715884105727",
+        "#t",
+        "#f",
+        "#nil",
+        "#\\a",
+        "#u8(1 2)",
+        "x",
+        "y",
+        "else",
+        "=>",
+        ".",
+        "...",
+        "_",
+        "#!optional",
+        "#!rest",
+        "#!key",
+        "k:",
+        "map",
+        "reduce",
+        "null?",
+        "and",
+        "when",
+        // format templates with huge parameters, in a directory that does
+        // not exist so that no file is ever created under their names
+        "\"/nonexistent/~1000000000000000000a\"",
+        "\"/nonexistent/~1,100000000000000F\"",
+    ];
+
+    /// What random character streams are made of, mostly characters that
+    /// mean something to the tokenizer and parser.
+    const FUZZ_CHARACTERS: [char; 24] = [
+        '(', ')', '(', ')', '"', '\\', '#', '\'', '.', ':', '~', ';', '-', '1', '9', 'u', '8', 'a',
+        'x', 'é', ' ', ' ', '\n', '\t',
+    ];
+
+    fn random_below(seed: &mut u64, bound: usize) -> usize {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        (*seed % bound as u64) as usize
+    }
+
+    /// An operation name, a word of the vocabulary or, now and then, the
+    /// name of the one file random programs may create. That name is the
+    /// only string they get, so no other file is ever written.
+    fn random_token(seed: &mut u64, file: &str, out: &mut String) {
+        let operations = value::OPERATION_NAMES;
+        match random_below(seed, 20) {
+            0 => out.push_str(file),
+            1..=10 => out.push_str(operations[random_below(seed, operations.len())].0),
+            _ => out.push_str(FUZZ_VOCABULARY[random_below(seed, FUZZ_VOCABULARY.len())]),
+        }
+    }
+
+    /// Builds a random expression, mostly well formed, from a xorshift seed.
+    fn random_expression(seed: &mut u64, depth: usize, file: &str, out: &mut String) {
+        if depth == 0 || random_below(seed, 3) == 0 {
+            random_token(seed, file, out);
+            return;
+        }
+        if random_below(seed, 20) != 0 {
+            out.push('(');
+        }
+        // most lists are calls, which get the furthest into the evaluator
+        if random_below(seed, 3) != 0 {
+            let operations = value::OPERATION_NAMES;
+            out.push_str(operations[random_below(seed, operations.len())].0);
+            out.push(' ');
+        }
+        for _ in 0..random_below(seed, 5) {
+            random_expression(seed, depth - 1, file, out);
+            out.push(' ');
+        }
+        if random_below(seed, 20) != 0 {
+            out.push(')');
+        }
+    }
+
+    /// Builds a random program: a random expression, one nested in up to
+    /// thrice as many lists as code may have, a stream of tokens and
+    /// parentheses with no structure, or a stream of characters.
+    fn random_program(seed: &mut u64, file: &str) -> String {
+        let mut code = String::new();
+        match random_below(seed, 4) {
+            0 => random_expression(seed, 4, file, &mut code),
+            1 => {
+                let depth = random_below(seed, 3 * parse::MAX_DEPTH);
+                code.push_str(&"(".repeat(depth));
+                random_expression(seed, 2, file, &mut code);
+                code.push_str(&")".repeat(depth));
+            }
+            2 => {
+                for _ in 0..random_below(seed, 16) {
+                    match random_below(seed, 4) {
+                        0 => code.push('('),
+                        1 => code.push(')'),
+                        _ => random_token(seed, file, &mut code),
+                    }
+                    code.push(' ');
+                }
+            }
+            _ => {
+                for _ in 0..random_below(seed, 40) {
+                    code.push(FUZZ_CHARACTERS[random_below(seed, FUZZ_CHARACTERS.len())]);
+                }
+            }
+        }
+        code
+    }
+
+    #[test]
+    fn malformed_programs_are_errors() {
+        let mut env = Env::new();
+        eval_file(&mut env, "std.scm").unwrap();
+        for code in [
+            "()",
+            "(if)",
+            "(car 1)",
+            "(let)",
+            "(let x)",
+            "(let ((x)) x)",
+            "(let ((1 2)) 1)",
+            "(lambda)",
+            "(define)",
+            "(define 1 2)",
+            "(set! 1 2)",
+            "(quote)",
+            "(cond (1))",
+            "(cond 1)",
+            "(guard)",
+            "(define-syntax)",
+            "(case-lambda (1))",
+            "(syntax-rules)",
+            "(macroexpand)",
+            "(1 2)",
+            "(* 170141183460469231731687303715884105727 2)",
+            "(pow 2 1000)",
+            "(pow 2 \"s\")",
+            "(dynamic-wind 1 2 3)",
+            "(call/cc 1)",
+            "(make-string 18446744073709551615)",
+            "(make-bytevector 9223372036854775807)",
+            "(iota 9223372036854775807)",
+            "(iota 9223372036854775807 0.5)",
+        ] {
+            assert!(
+                eval_str(&mut env, code).is_err(),
+                "{} should be an error",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn random_programs_never_panic() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        let path = std::env::temp_dir().join(format!("fuzz-{}.txt", std::process::id()));
+        let file = format!("{:?}", path.to_str().unwrap());
+        for _ in 0..10_000 {
+            // fresh ports and a fresh environment each time, a continuation
+            // kept from an earlier program could loop forever
+            port::set_current_output(std::rc::Rc::new(port::Port::StringOutput(
+                Default::default(),
+            )));
+            port::set_current_input(std::rc::Rc::new(port::Port::string_input(
+                "(1 \"s\" #(x)) x",
+            )));
+            let mut env = Env::new();
+            eval_file(&mut env, "std.scm").unwrap();
+            let code = random_program(&mut seed, &file);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let _ = eval_str(&mut env, &code);
+            }));
+            assert!(result.is_ok(), "panicked on {}", code);
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    /// Arguments every operation is applied to besides a file name, the
+    /// ones that tend to be out of range or of the wrong type.
+    const FUZZ_ARGUMENTS: [&str; 11] = [
+        "-1",
+        "2.5",
+        "9223372036854775807",
+        "18446744073709551615",
+        "#\\a",
+        "#u8(1 2)",
+        "(list 1 2)",
+        "(vector 1)",
+        "(quote big)",
+        "(open-input-string \"(a\")",
+        "car",
+    ];
+
+    #[test]
+    fn operations_never_panic_on_odd_arguments() {
+        port::set_current_output(std::rc::Rc::new(port::Port::StringOutput(
+            Default::default(),
+        )));
+        port::set_current_input(std::rc::Rc::new(port::Port::string_input("")));
+        let path = std::env::temp_dir().join(format!("sweep-{}.txt", std::process::id()));
+        let mut pool = FUZZ_ARGUMENTS.map(String::from).to_vec();
+        pool.push(format!("{:?}", path.to_str().unwrap()));
+        let mut arguments = vec![String::new()];
+        for count in 1..=3 {
+            for index in 0..pool.len().pow(count) {
+                let mut rest = index;
+                let mut combination = String::new();
+                for _ in 0..count {
+                    combination.push(' ');
+                    combination.push_str(&pool[rest % pool.len()]);
+                    rest /= pool.len();
+                }
+                arguments.push(combination);
+            }
+        }
+        for (name, _) in value::OPERATION_NAMES {
+            let mut env = Env::new();
+            for arguments in &arguments {
+                let code = format!("({}{})", name, arguments);
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    let _ = eval_str(&mut env, &code);
+                }));
+                assert!(result.is_ok(), "panicked on {}", code);
+            }
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_let() {
+        let code = "(let ((a 5) (b (+ 5 a))) (+ a b))";
+        let mut env = Env::new();
+        assert_eq!(eval_str(&mut env, code).unwrap(), Value::Int(15));
+    }
 }
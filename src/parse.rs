@@ -1,146 +1,153 @@
-use crate::env::Env;
+use crate::error::{InterpreterError, Result};
+use crate::symbol::Symbol;
 use crate::tokenize::Token;
 use std::collections::VecDeque;
 use std::fmt::Display;
+use std::rc::Rc;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub enum Operation {
-    Add,
-    Substract,
-    Divide,
-    Multiply,
-    Pow,
-    Exp,
-    Car,
-    Cdr,
-    Cons,
-    List,
-    Begin,
-    Module,
-    Cond,
-    If,
-    Eq,
-    Smaller,
-    Greater,
-    SmallerOrEqual,
-    GreaterOrEqual,
-    Define,
-    Set,
-    Lambda,
-    Quote,
-    Let,
-}
-
-impl Operation {
-    fn get(operation_name: &str) -> Option<Operation> {
-        match operation_name {
-            "+" => Some(Operation::Add),
-            "-" => Some(Operation::Substract),
-            "*" => Some(Operation::Multiply),
-            "/" => Some(Operation::Divide),
-            "pow" => Some(Operation::Pow),
-            "exp" => Some(Operation::Exp),
-            "car" => Some(Operation::Car),
-            "cdr" => Some(Operation::Cdr),
-            "cons" => Some(Operation::Cons),
-            "list" => Some(Operation::List),
-            "begin" => Some(Operation::Begin),
-            "module" => Some(Operation::Module),
-            "cond" => Some(Operation::Cond),
-            "if" => Some(Operation::If),
-            "=" => Some(Operation::Eq),
-            "<" => Some(Operation::Smaller),
-            ">" => Some(Operation::Greater),
-            "<=" => Some(Operation::SmallerOrEqual),
-            ">=" => Some(Operation::GreaterOrEqual),
-            "define" => Some(Operation::Define),
-            "set!" => Some(Operation::Set),
-            "lambda" => Some(Operation::Lambda),
-            "quote" => Some(Operation::Quote),
-            "let" => Some(Operation::Let),
-            _ => None,
-        }
-    }
-}
-
+/// Syntax tree produced by `parse`. Code is evaluated from datums, and
+/// `quote` turns a datum into a runtime `Value`.
 #[derive(Debug, Clone, PartialEq)]
-pub enum SymbolicExpression {
-    Str(String),
-    Symbol(String),
+pub enum Datum {
+    Str(Rc<str>),
+    Symbol(Symbol),
     Float(f64),
     Int(i128),
     Bool(bool),
-    Cons {
-        head: Box<SymbolicExpression>,
-        tail: Box<SymbolicExpression>,
-    },
+    Char(char),
+    /// `#u8(...)`, a literal bytevector.
+    Bytevector(Rc<[u8]>),
     Nil,
-    Expression(Vec<SymbolicExpression>),
-    Lambda {
-        parameters: Vec<String>,
-        env: Env,
-        body: Box<SymbolicExpression>,
-    },
-    Operation(Operation),
-}
-
-impl PartialOrd for SymbolicExpression {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (Self::Str(left), Self::Str(right)) => left.partial_cmp(right),
-            (Self::Float(left), Self::Float(right)) => left.partial_cmp(right),
-            (Self::Int(left), Self::Int(right)) => left.partial_cmp(right),
-            (Self::Int(left), Self::Float(right)) => (*left as f64).partial_cmp(right),
-            (Self::Float(left), Self::Int(right)) => left.partial_cmp(&(*right as f64)),
-            _ => None,
-        }
-    }
+    /// `name:`, written before the argument of a keyword parameter.
+    Keyword(Symbol),
+    List(Rc<[Datum]>),
 }
 
-impl Display for SymbolicExpression {
+impl Display for Datum {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Float(value) => write!(f, "{}", value),
             Self::Int(value) => write!(f, "{}", value),
             Self::Str(value) => write!(f, "{}", value),
-            Self::Cons { head, tail } => write!(f, "({} . {})", head, tail),
-            Self::Symbol(value) => write!(f, "#{}", value),
+            Self::Symbol(value) => write!(f, "{}", value),
             Self::Bool(value) => write!(f, "{}", if *value { "#t" } else { "#f" }),
+            Self::Char(value) => match CHAR_NAMES.iter().find(|(_, char)| char == value) {
+                Some((name, _)) => write!(f, "#\\{}", name),
+                None => write!(f, "#\\{}", value),
+            },
+            Self::Bytevector(bytes) => {
+                write!(f, "#u8(")?;
+                for (index, byte) in bytes.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", byte)?;
+                }
+                write!(f, ")")
+            }
             Self::Nil => write!(f, "#nil"),
-            Self::Expression(values) => write!(f, "({:?})", values),
-            Self::Lambda {
-                parameters, body, ..
-            } => {
-                write!(f, "(lambda ({:?}) ({:?}))", parameters, body)
+            Self::Keyword(name) => write!(f, "{}:", name),
+            Self::List(values) => {
+                write!(f, "(")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, ")")
             }
-            Self::Operation(operation) => write!(f, "{:?}", operation),
         }
     }
 }
 
-pub fn parse(tokens: &mut VecDeque<Token>) -> SymbolicExpression {
+/// Characters written by name, since `#\ ` would be split like any token.
+pub const CHAR_NAMES: [(&str, char); 4] = [
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("nul", '\0'),
+];
+
+/// Reads the part of a character literal after `#\`.
+fn parse_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(char), None) => Some(char),
+        _ => CHAR_NAMES
+            .iter()
+            .find(|(name, _)| *name == text)
+            .map(|(_, char)| *char),
+    }
+}
+
+/// The bytes of the list after `#u8`, unless it holds something else.
+fn bytes(list: &Datum) -> Option<Rc<[u8]>> {
+    match list {
+        Datum::List(values) => values
+            .iter()
+            .map(|value| match value {
+                Datum::Int(byte) => u8::try_from(*byte).ok(),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// How deeply lists may nest. Everything that walks a datum recurses into
+/// its lists, so deeper code would overflow the stack.
+pub const MAX_DEPTH: usize = 512;
+
+/// Parses the tokens up to the first unmatched `)` into a list.
+pub fn parse(tokens: &mut VecDeque<Token>) -> Result<Datum> {
+    parse_list(tokens, 1)
+}
+
+fn parse_list(tokens: &mut VecDeque<Token>, depth: usize) -> Result<Datum> {
+    if depth > MAX_DEPTH {
+        return Err(InterpreterError::ReadError(format!(
+            "lists nested more than {} deep",
+            MAX_DEPTH
+        )));
+    }
     let mut values = Vec::new();
     while let Some(token) = tokens.pop_front() {
         let value = match token {
+            // a bytevector literal with anything but bytes in it is left as
+            // the symbol `#u8` followed by a list
+            Token::Symbol(value)
+                if value == "#u8" && matches!(tokens.front(), Some(Token::LeftParanthesis)) =>
+            {
+                tokens.pop_front();
+                let list = parse_list(tokens, depth + 1)?;
+                match bytes(&list) {
+                    Some(bytes) => Datum::Bytevector(bytes),
+                    None => {
+                        values.push(Datum::Symbol(Symbol::intern(&value)));
+                        list
+                    }
+                }
+            }
             Token::RightParanthesis => break,
-            Token::LeftParanthesis => parse(tokens),
-            Token::Float(value) => SymbolicExpression::Float(value),
-            Token::Int(value) => SymbolicExpression::Int(value),
-            Token::String(value) => SymbolicExpression::Str(value),
+            Token::LeftParanthesis => parse_list(tokens, depth + 1)?,
+            Token::Float(value) => Datum::Float(value),
+            Token::Int(value) => Datum::Int(value),
+            Token::String(value) => Datum::Str(value.into()),
             Token::Symbol(value) => match value.as_str() {
-                "#nil" => SymbolicExpression::Nil,
-                "#t" => SymbolicExpression::Bool(true),
-                "#f" => SymbolicExpression::Bool(false),
-                _ => {
-                    if let Some(operation) = Operation::get(&value) {
-                        SymbolicExpression::Operation(operation)
-                    } else {
-                        SymbolicExpression::Symbol(value)
-                    }
+                "#nil" => Datum::Nil,
+                "#t" => Datum::Bool(true),
+                "#f" => Datum::Bool(false),
+                _ if value.starts_with("#\\") => parse_char(&value[2..])
+                    .map(Datum::Char)
+                    .unwrap_or_else(|| Datum::Symbol(Symbol::intern(&value))),
+                _ if value.len() > 1 && value.ends_with(':') => {
+                    Datum::Keyword(Symbol::intern(&value[..value.len() - 1]))
                 }
+                _ => Datum::Symbol(Symbol::intern(&value)),
             },
         };
         values.push(value);
     }
-    SymbolicExpression::Expression(values)
+    Ok(Datum::List(values.into()))
 }
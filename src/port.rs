@@ -0,0 +1,793 @@
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::rc::Rc;
+
+use crate::bytevectors::{byte, byte_slice, bytevector};
+use crate::error::{InterpreterError, Result};
+use crate::eval::{file_name, not_a, slice_bounds};
+use crate::parse::{parse, Datum, CHAR_NAMES};
+use crate::tokenize::tokenize;
+use crate::value::{Operation, Value};
+
+/// Where `display` and friends write characters to and `read-char` and
+/// friends read them from.
+#[derive(Debug)]
+pub enum Port {
+    Stdout,
+    /// `None` once the port is closed.
+    File(RefCell<Option<BufWriter<File>>>),
+    /// Collects what is written to it for `get-output-string`.
+    StringOutput(RefCell<String>),
+    Input(RefCell<TextInput>),
+    /// Collects the bytes written to it for `get-output-bytevector`.
+    BytesOutput(RefCell<Vec<u8>>),
+    BinaryInput(RefCell<BinaryInput>),
+}
+
+#[derive(Debug)]
+pub enum BinaryInput {
+    Bytes(Cursor<Vec<u8>>),
+    File(BufReader<File>),
+    Closed,
+}
+
+impl BinaryInput {
+    fn reader(&mut self) -> Result<&mut dyn BufRead> {
+        match self {
+            BinaryInput::Bytes(bytes) => Ok(bytes),
+            BinaryInput::File(file) => Ok(file),
+            BinaryInput::Closed => Err(InterpreterError::IoError(
+                "cannot read from a closed port".into(),
+            )),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self
+            .reader()?
+            .fill_buf()
+            .map_err(io_error)?
+            .first()
+            .copied())
+    }
+
+    fn next(&mut self) -> Result<Option<u8>> {
+        let byte = self.peek()?;
+        if byte.is_some() {
+            self.reader()?.consume(1);
+        }
+        Ok(byte)
+    }
+
+    /// Up to `count` bytes, fewer only at the end of the input.
+    fn bytes(&mut self, count: usize) -> Result<Option<Vec<u8>>> {
+        let mut bytes = Vec::new();
+        self.reader()?
+            .take(count as u64)
+            .read_to_end(&mut bytes)
+            .map_err(io_error)?;
+        Ok((count == 0 || !bytes.is_empty()).then_some(bytes))
+    }
+}
+
+/// Where an input port gets more text once it has read what it has.
+#[derive(Debug)]
+enum Source {
+    /// A string port has all of its text from the start.
+    Nothing,
+    Stdin,
+    File(BufReader<File>),
+    Closed,
+}
+
+/// Text being read, a line at a time unless it all came from a string,
+/// and the byte offset of the next character.
+#[derive(Debug)]
+pub struct TextInput {
+    text: String,
+    position: usize,
+    source: Source,
+}
+
+fn end_of_input() -> InterpreterError {
+    InterpreterError::ReadError("unexpected end of input".into())
+}
+
+fn io_error(error: io::Error) -> InterpreterError {
+    InterpreterError::IoError(error.to_string())
+}
+
+impl TextInput {
+    /// Reads the next line from the source once the text is used up,
+    /// returns whether there is anything left to read.
+    fn fill(&mut self) -> Result<bool> {
+        if self.position < self.text.len() {
+            return Ok(true);
+        }
+        self.text.clear();
+        self.position = 0;
+        match &mut self.source {
+            Source::Nothing => {}
+            Source::Stdin => {
+                io::stdin().read_line(&mut self.text).map_err(io_error)?;
+            }
+            Source::File(reader) => {
+                reader.read_line(&mut self.text).map_err(io_error)?;
+            }
+            Source::Closed => {
+                return Err(InterpreterError::IoError(
+                    "cannot read from a closed port".into(),
+                ))
+            }
+        }
+        Ok(!self.text.is_empty())
+    }
+
+    fn peek(&mut self) -> Result<Option<char>> {
+        self.fill()?;
+        Ok(self.text[self.position..].chars().next())
+    }
+
+    fn next(&mut self) -> Result<Option<char>> {
+        let char = self.peek()?;
+        self.position += char.map_or(0, char::len_utf8);
+        Ok(char)
+    }
+
+    /// The characters up to the next newline, which is consumed but not
+    /// returned.
+    fn line(&mut self) -> Result<Option<String>> {
+        if !self.fill()? {
+            return Ok(None);
+        }
+        let rest = &self.text[self.position..];
+        let (line, consumed) = match rest.find('\n') {
+            Some(end) => (&rest[..end], end + 1),
+            None => (rest, rest.len()),
+        };
+        let line = line.to_string();
+        self.position += consumed;
+        Ok(Some(line))
+    }
+
+    /// Up to `count` characters, fewer only at the end of the input.
+    fn string(&mut self, count: usize) -> Result<Option<String>> {
+        let mut string = String::new();
+        for _ in 0..count {
+            match self.next()? {
+                Some(char) => string.push(char),
+                None => break,
+            }
+        }
+        Ok((count == 0 || !string.is_empty()).then_some(string))
+    }
+
+    /// Reads the rest of a string literal after its opening quote into
+    /// `text`.
+    fn string_literal(&mut self, text: &mut String) -> Result<()> {
+        loop {
+            let char = self.next()?.ok_or_else(end_of_input)?;
+            text.push(char);
+            match char {
+                '"' => return Ok(()),
+                '\\' => text.push(self.next()?.ok_or_else(end_of_input)?),
+                _ => {}
+            }
+        }
+    }
+
+    /// The text of the next datum, `None` at the end of the input.
+    fn datum(&mut self) -> Result<Option<String>> {
+        while self.peek()?.is_some_and(char::is_whitespace) {
+            self.next()?;
+        }
+        let mut text = String::new();
+        let mut depth = 0;
+        loop {
+            let Some(char) = self.next()? else {
+                return match (text.is_empty(), depth) {
+                    (true, _) => Ok(None),
+                    (false, 0) => Ok(Some(text)),
+                    _ => Err(end_of_input()),
+                };
+            };
+            text.push(char);
+            match char {
+                '(' => depth += 1,
+                ')' if depth == 0 => {
+                    return Err(InterpreterError::ReadError("unexpected )".into()))
+                }
+                ')' => depth -= 1,
+                '"' => self.string_literal(&mut text)?,
+                // the character of `#\(` doesn't open a list
+                '#' if self.peek()? == Some('\\') => {
+                    text.extend(self.next()?);
+                    text.extend(self.next()?);
+                }
+                _ => {}
+            }
+            if depth == 0 {
+                let next = self.peek()?;
+                let atom_ends =
+                    next.is_none_or(|next| next.is_whitespace() || matches!(next, '(' | ')' | '"'));
+                let bytevector = text == "#u8" && next == Some('(');
+                if matches!(char, ')' | '"') || (atom_ends && !bytevector) {
+                    return Ok(Some(text));
+                }
+            }
+        }
+    }
+
+    /// Whether reading a character won't wait for input. Only standard
+    /// input ever makes a reader wait.
+    fn is_ready(&self) -> bool {
+        !matches!(self.source, Source::Stdin) || self.position < self.text.len()
+    }
+}
+
+impl Port {
+    fn input_from(text: String, source: Source) -> Port {
+        Port::Input(RefCell::new(TextInput {
+            text,
+            position: 0,
+            source,
+        }))
+    }
+
+    pub fn string_input(text: impl Into<String>) -> Port {
+        Port::input_from(text.into(), Source::Nothing)
+    }
+
+    pub fn stdin() -> Port {
+        Port::input_from(String::new(), Source::Stdin)
+    }
+
+    pub fn open_input_file(name: &str) -> Result<Port> {
+        let file = File::open(name).map_err(|error| {
+            InterpreterError::IoError(format!("cannot open {}: {}", name, error))
+        })?;
+        Ok(Port::input_from(
+            String::new(),
+            Source::File(BufReader::new(file)),
+        ))
+    }
+
+    pub fn open_binary_input_file(name: &str) -> Result<Port> {
+        let file = File::open(name).map_err(|error| {
+            InterpreterError::IoError(format!("cannot open {}: {}", name, error))
+        })?;
+        Ok(Port::BinaryInput(RefCell::new(BinaryInput::File(
+            BufReader::new(file),
+        ))))
+    }
+
+    pub fn open_output_file(name: &str) -> Result<Port> {
+        let file = File::create(name).map_err(|error| {
+            InterpreterError::IoError(format!("cannot open {}: {}", name, error))
+        })?;
+        Ok(Port::File(RefCell::new(Some(BufWriter::new(file)))))
+    }
+
+    pub fn write_str(&self, text: &str) -> Result<()> {
+        match self {
+            Port::Stdout => io::stdout().write_all(text.as_bytes()).map_err(io_error),
+            Port::File(file) => match file.borrow_mut().as_mut() {
+                Some(file) => file.write_all(text.as_bytes()).map_err(io_error),
+                None => Err(InterpreterError::IoError(
+                    "cannot write to a closed port".into(),
+                )),
+            },
+            Port::StringOutput(output) => {
+                output.borrow_mut().push_str(text);
+                Ok(())
+            }
+            Port::BytesOutput(_) => Err(InterpreterError::ValueError(
+                "cannot write characters to a binary port".into(),
+            )),
+            Port::Input(_) | Port::BinaryInput(_) => Err(InterpreterError::ValueError(
+                "cannot write to an input port".into(),
+            )),
+        }
+    }
+
+    pub fn write_bytes(&self, bytes: &[u8]) -> Result<()> {
+        match self {
+            Port::Stdout => io::stdout().write_all(bytes).map_err(io_error),
+            Port::File(file) => match file.borrow_mut().as_mut() {
+                Some(file) => file.write_all(bytes).map_err(io_error),
+                None => Err(InterpreterError::IoError(
+                    "cannot write to a closed port".into(),
+                )),
+            },
+            Port::BytesOutput(output) => {
+                output.borrow_mut().extend_from_slice(bytes);
+                Ok(())
+            }
+            Port::StringOutput(_) => Err(InterpreterError::ValueError(
+                "cannot write bytes to a textual port".into(),
+            )),
+            Port::Input(_) | Port::BinaryInput(_) => Err(InterpreterError::ValueError(
+                "cannot write to an input port".into(),
+            )),
+        }
+    }
+
+    /// Sends what has been written on, which only happens at the end of
+    /// each line of standard output and when a file's buffer is full
+    /// otherwise.
+    pub fn flush(&self) -> Result<()> {
+        match self {
+            Port::Stdout => io::stdout().flush().map_err(io_error),
+            Port::File(file) => match file.borrow_mut().as_mut() {
+                Some(file) => file.flush().map_err(io_error),
+                None => Ok(()),
+            },
+            Port::StringOutput(_) | Port::BytesOutput(_) => Ok(()),
+            Port::Input(_) | Port::BinaryInput(_) => Err(InterpreterError::ValueError(
+                "cannot flush an input port".into(),
+            )),
+        }
+    }
+
+    /// Flushes a file and releases it, later reads and writes are errors.
+    /// Closing a port twice or closing a console or string port does
+    /// nothing.
+    pub fn close(&self) -> Result<()> {
+        match self {
+            Port::File(file) => match file.borrow_mut().take() {
+                Some(mut file) => file.flush().map_err(io_error),
+                None => Ok(()),
+            },
+            Port::Input(input) => {
+                let mut input = input.borrow_mut();
+                if matches!(input.source, Source::File(_)) {
+                    input.source = Source::Closed;
+                    input.text.clear();
+                    input.position = 0;
+                }
+                Ok(())
+            }
+            Port::BinaryInput(input) => {
+                let mut input = input.borrow_mut();
+                if matches!(*input, BinaryInput::File(_)) {
+                    *input = BinaryInput::Closed;
+                }
+                Ok(())
+            }
+            Port::Stdout | Port::StringOutput(_) | Port::BytesOutput(_) => Ok(()),
+        }
+    }
+
+    /// What has been written to a string output port so far.
+    pub fn output_string(&self) -> Result<String> {
+        match self {
+            Port::StringOutput(output) => Ok(output.borrow().clone()),
+            _ => Err(InterpreterError::ValueError(
+                "get-output-string takes a string output port".into(),
+            )),
+        }
+    }
+
+    fn input(&self) -> Result<RefMut<'_, TextInput>> {
+        match self {
+            Port::Input(input) => Ok(input.borrow_mut()),
+            Port::BinaryInput(_) => Err(InterpreterError::ValueError(
+                "cannot read characters from a binary port".into(),
+            )),
+            _ => Err(InterpreterError::ValueError(
+                "cannot read from an output port".into(),
+            )),
+        }
+    }
+
+    fn binary_input(&self) -> Result<RefMut<'_, BinaryInput>> {
+        match self {
+            Port::BinaryInput(input) => Ok(input.borrow_mut()),
+            Port::Input(_) => Err(InterpreterError::ValueError(
+                "cannot read bytes from a textual port".into(),
+            )),
+            _ => Err(InterpreterError::ValueError(
+                "cannot read from an output port".into(),
+            )),
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT_OUTPUT: RefCell<Rc<Port>> = RefCell::new(Rc::new(Port::Stdout));
+    static CURRENT_INPUT: RefCell<Rc<Port>> =
+        RefCell::new(Rc::new(Port::stdin()));
+}
+
+/// The port output procedures write to when they aren't given one.
+pub fn current_output() -> Rc<Port> {
+    CURRENT_OUTPUT.with(|port| port.borrow().clone())
+}
+
+/// Makes `port` the current output port and returns the previous one.
+pub fn set_current_output(port: Rc<Port>) -> Rc<Port> {
+    CURRENT_OUTPUT.with(|current| current.replace(port))
+}
+
+pub fn current_input() -> Rc<Port> {
+    CURRENT_INPUT.with(|port| port.borrow().clone())
+}
+
+/// Makes `port` the current input port, so that tests don't wait on
+/// standard input.
+#[cfg(test)]
+pub fn set_current_input(port: Rc<Port>) -> Rc<Port> {
+    CURRENT_INPUT.with(|current| current.replace(port))
+}
+
+/// How `print` writes values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// Strings and characters as their contents, for `display`.
+    Display,
+    /// Strings and characters quoted so that `read` can get them back. Pairs
+    /// are immutable, so no structure can contain itself and `write` never
+    /// needs labels.
+    Write,
+    /// Like `Write`, with a `#n=` label on every pair or vector that occurs
+    /// more than once and `#n#` where it occurs again.
+    Shared,
+}
+
+/// What makes a pair or vector the same object as another. Two pairs
+/// holding the same head and tail cells can't be told apart.
+fn identity(value: &Value) -> Option<(usize, usize)> {
+    match value {
+        Value::Cons { head, tail } => Some((Rc::as_ptr(head) as usize, Rc::as_ptr(tail) as usize)),
+        Value::Vector(values) => Some((Rc::as_ptr(values) as *const () as usize, 0)),
+        _ => None,
+    }
+}
+
+/// Records in `seen` the pairs and vectors of `value`, as `true` for the
+/// ones reached more than once.
+fn find_shared(value: &Value, seen: &mut HashMap<(usize, usize), bool>) {
+    let Some(identity) = identity(value) else {
+        return;
+    };
+    if let Some(shared) = seen.get_mut(&identity) {
+        *shared = true;
+        return;
+    }
+    seen.insert(identity, false);
+    match value {
+        Value::Cons { head, tail } => {
+            find_shared(head, seen);
+            find_shared(tail, seen);
+        }
+        Value::Vector(values) => {
+            for value in values.borrow().iter() {
+                find_shared(value, seen);
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Printer<'a> {
+    out: &'a mut String,
+    style: Style,
+    /// The shared pairs and vectors, with their label once it is printed.
+    labels: HashMap<(usize, usize), Option<usize>>,
+    next_label: usize,
+}
+
+impl Printer<'_> {
+    /// Prints the label of a shared `value`, returns whether it was printed
+    /// before and only needs the reference.
+    fn label(&mut self, value: &Value) -> bool {
+        let Some(label) = identity(value).and_then(|identity| self.labels.get_mut(&identity))
+        else {
+            return false;
+        };
+        match label {
+            Some(label) => {
+                self.out.push_str(&format!("#{}#", label));
+                true
+            }
+            None => {
+                *label = Some(self.next_label);
+                self.out.push_str(&format!("#{}=", self.next_label));
+                self.next_label += 1;
+                false
+            }
+        }
+    }
+
+    fn is_shared(&self, value: &Value) -> bool {
+        identity(value).is_some_and(|identity| self.labels.contains_key(&identity))
+    }
+
+    fn print(&mut self, value: &Value) {
+        if self.label(value) {
+            return;
+        }
+        let write = self.style != Style::Display;
+        match value {
+            Value::Str(string) if write => {
+                self.out.push('"');
+                for char in string.borrow().chars() {
+                    match char {
+                        '"' => self.out.push_str("\\\""),
+                        '\\' => self.out.push_str("\\\\"),
+                        '\n' => self.out.push_str("\\n"),
+                        '\t' => self.out.push_str("\\t"),
+                        '\r' => self.out.push_str("\\r"),
+                        char => self.out.push(char),
+                    }
+                }
+                self.out.push('"');
+            }
+            Value::Char(char) if write => {
+                self.out.push_str("#\\");
+                match CHAR_NAMES.iter().find(|(_, named)| named == char) {
+                    Some((name, _)) => self.out.push_str(name),
+                    None => self.out.push(*char),
+                }
+            }
+            Value::Symbol(symbol) => self.out.push_str(&symbol.name()),
+            Value::Nil => self.out.push_str("()"),
+            Value::Cons { head, tail } => {
+                self.out.push('(');
+                self.print(head);
+                let mut current = tail.as_ref();
+                loop {
+                    match current {
+                        Value::Nil => break,
+                        Value::Cons { head, tail } if !self.is_shared(current) => {
+                            self.out.push(' ');
+                            self.print(head);
+                            current = tail;
+                        }
+                        tail => {
+                            self.out.push_str(" . ");
+                            self.print(tail);
+                            break;
+                        }
+                    }
+                }
+                self.out.push(')');
+            }
+            Value::Vector(values) => {
+                self.out.push_str("#(");
+                for (index, value) in values.borrow().iter().enumerate() {
+                    if index > 0 {
+                        self.out.push(' ');
+                    }
+                    self.print(value);
+                }
+                self.out.push(')');
+            }
+            value => self.out.push_str(&value.to_string()),
+        }
+    }
+}
+
+/// Appends the external representation of `value` to `out`.
+pub fn print(out: &mut String, value: &Value, style: Style) {
+    let mut labels = HashMap::new();
+    if style == Style::Shared {
+        let mut seen = HashMap::new();
+        find_shared(value, &mut seen);
+        labels = seen
+            .into_iter()
+            .filter(|(_, shared)| *shared)
+            .map(|(identity, _)| (identity, None))
+            .collect();
+    }
+    Printer {
+        out,
+        style,
+        labels,
+        next_label: 0,
+    }
+    .print(value);
+}
+
+/// The port given as the argument at `index`, or the current one.
+fn port(arguments: &[Value], index: usize, current: fn() -> Rc<Port>) -> Result<Rc<Port>> {
+    match arguments.get(index) {
+        Some(Value::Port(port)) => Ok(port.clone()),
+        Some(value) => Err(not_a("port", value)),
+        None => Ok(current()),
+    }
+}
+
+fn count(value: &Value) -> Result<usize> {
+    match value {
+        Value::Int(count) => usize::try_from(*count).map_err(|_| not_a("count", value)),
+        value => Err(not_a("count", value)),
+    }
+}
+
+fn or_eof(value: Option<Value>) -> Value {
+    value.unwrap_or(Value::Eof)
+}
+
+/// Applies the port procedures that don't call a procedure argument.
+pub fn apply_port_primitive(operation: Operation, arguments: &[Value]) -> Result<Value> {
+    match operation {
+        Operation::OpenOutputString => {
+            Ok(Value::Port(Rc::new(Port::StringOutput(RefCell::default()))))
+        }
+        Operation::OpenInputString => match &arguments[0] {
+            Value::Str(text) => Ok(Value::Port(Rc::new(Port::string_input(
+                text.borrow().as_str(),
+            )))),
+            value => Err(not_a("string", value)),
+        },
+        Operation::GetOutputString => match &arguments[0] {
+            Value::Port(port) => Ok(Value::string(port.output_string()?)),
+            value => Err(not_a("port", value)),
+        },
+        Operation::Display | Operation::Write | Operation::WriteShared | Operation::WriteSimple => {
+            let style = match operation {
+                Operation::Display => Style::Display,
+                Operation::WriteShared => Style::Shared,
+                _ => Style::Write,
+            };
+            let mut out = String::new();
+            print(&mut out, &arguments[0], style);
+            port(arguments, 1, current_output)?.write_str(&out)?;
+            Ok(Value::Nil)
+        }
+        Operation::WriteChar => match &arguments[0] {
+            Value::Char(char) => {
+                port(arguments, 1, current_output)?.write_str(char.encode_utf8(&mut [0; 4]))?;
+                Ok(Value::Nil)
+            }
+            value => Err(not_a("character", value)),
+        },
+        Operation::WriteString => match &arguments[0] {
+            Value::Str(string) => {
+                let string = string.borrow();
+                let (start, end) = slice_bounds(
+                    arguments.get(2..).unwrap_or_default(),
+                    string.chars().count(),
+                )?;
+                let text = string
+                    .chars()
+                    .skip(start)
+                    .take(end - start)
+                    .collect::<String>();
+                port(arguments, 1, current_output)?.write_str(&text)?;
+                Ok(Value::Nil)
+            }
+            value => Err(not_a("string", value)),
+        },
+        Operation::FlushOutputPort => {
+            port(arguments, 0, current_output)?.flush()?;
+            Ok(Value::Nil)
+        }
+        Operation::CurrentOutputPort => Ok(Value::Port(current_output())),
+        Operation::Newline => {
+            port(arguments, 0, current_output)?.write_str("\n")?;
+            Ok(Value::Nil)
+        }
+        Operation::ReadChar => Ok(or_eof(
+            port(arguments, 0, current_input)?
+                .input()?
+                .next()?
+                .map(Value::Char),
+        )),
+        Operation::PeekChar => Ok(or_eof(
+            port(arguments, 0, current_input)?
+                .input()?
+                .peek()?
+                .map(Value::Char),
+        )),
+        Operation::ReadLine => Ok(or_eof(
+            port(arguments, 0, current_input)?
+                .input()?
+                .line()?
+                .map(Value::string),
+        )),
+        Operation::ReadString => {
+            let count = count(&arguments[0])?;
+            Ok(or_eof(
+                port(arguments, 1, current_input)?
+                    .input()?
+                    .string(count)?
+                    .map(Value::string),
+            ))
+        }
+        Operation::IsCharReady => Ok(Value::Bool(
+            port(arguments, 0, current_input)?.input()?.is_ready(),
+        )),
+        Operation::OpenInputFile => Ok(Value::Port(Rc::new(Port::open_input_file(&file_name(
+            &arguments[0],
+        )?)?))),
+        Operation::OpenOutputFile => Ok(Value::Port(Rc::new(Port::open_output_file(&file_name(
+            &arguments[0],
+        )?)?))),
+        Operation::OpenInputBytevector => {
+            Ok(Value::Port(Rc::new(Port::BinaryInput(RefCell::new(
+                BinaryInput::Bytes(Cursor::new(bytevector(&arguments[0])?.borrow().clone())),
+            )))))
+        }
+        Operation::OpenOutputBytevector => {
+            Ok(Value::Port(Rc::new(Port::BytesOutput(RefCell::default()))))
+        }
+        Operation::GetOutputBytevector => match &arguments[0] {
+            Value::Port(port) => match port.as_ref() {
+                Port::BytesOutput(bytes) => Ok(Value::bytevector(bytes.borrow().clone())),
+                _ => Err(InterpreterError::ValueError(
+                    "get-output-bytevector takes a bytevector output port".into(),
+                )),
+            },
+            value => Err(not_a("port", value)),
+        },
+        Operation::OpenBinaryInputFile => Ok(Value::Port(Rc::new(Port::open_binary_input_file(
+            &file_name(&arguments[0])?,
+        )?))),
+        Operation::OpenBinaryOutputFile => Ok(Value::Port(Rc::new(Port::open_output_file(
+            &file_name(&arguments[0])?,
+        )?))),
+        Operation::ReadU8 => Ok(or_eof(
+            port(arguments, 0, current_input)?
+                .binary_input()?
+                .next()?
+                .map(|byte| Value::Int(i128::from(byte))),
+        )),
+        Operation::PeekU8 => Ok(or_eof(
+            port(arguments, 0, current_input)?
+                .binary_input()?
+                .peek()?
+                .map(|byte| Value::Int(i128::from(byte))),
+        )),
+        Operation::IsU8Ready => {
+            port(arguments, 0, current_input)?.binary_input()?;
+            Ok(Value::Bool(true))
+        }
+        Operation::ReadBytevector => {
+            let count = count(&arguments[0])?;
+            Ok(or_eof(
+                port(arguments, 1, current_input)?
+                    .binary_input()?
+                    .bytes(count)?
+                    .map(Value::bytevector),
+            ))
+        }
+        Operation::WriteU8 => {
+            let byte = byte(&arguments[0])?;
+            port(arguments, 1, current_output)?.write_bytes(&[byte])?;
+            Ok(Value::Nil)
+        }
+        Operation::WriteBytevector => {
+            let bytes = bytevector(&arguments[0])?;
+            let bytes = bytes.borrow();
+            port(arguments, 1, current_output)?
+                .write_bytes(byte_slice(&bytes, arguments.get(2..).unwrap_or_default())?)?;
+            Ok(Value::Nil)
+        }
+        Operation::ClosePort => match &arguments[0] {
+            Value::Port(port) => {
+                port.close()?;
+                Ok(Value::Nil)
+            }
+            value => Err(not_a("port", value)),
+        },
+        Operation::Read => {
+            let Some(text) = port(arguments, 0, current_input)?.input()?.datum()? else {
+                return Ok(Value::Eof);
+            };
+            match parse(&mut tokenize(&text))? {
+                Datum::List(data) if data.len() == 1 => Ok(Value::from(&data[0])),
+                _ => Err(InterpreterError::ReadError(format!("cannot read {}", text))),
+            }
+        }
+        Operation::EofObject => Ok(Value::Eof),
+        Operation::IsEofObject => Ok(Value::Bool(matches!(arguments[0], Value::Eof))),
+        _ => Err(InterpreterError::RuntimeError(format!(
+            "{} is not a port primitive",
+            operation.name()
+        ))),
+    }
+}
@@ -0,0 +1,133 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use regex::{Captures, Regex};
+
+use crate::error::{InterpreterError, Result};
+use crate::eval::not_a;
+use crate::value::{Operation, Value};
+
+/// Compiled patterns by source, so passing the same pattern string again
+/// doesn't compile it again.
+#[derive(Default)]
+struct Cache {
+    regexes: HashMap<String, Rc<Regex>>,
+}
+
+/// Patterns scripts use are few, a cache this large is only full when
+/// patterns are built at runtime, and then it starts over.
+const CACHE_CAPACITY: usize = 256;
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(Cache::default());
+}
+
+pub fn compile(pattern: &str) -> Result<Rc<Regex>> {
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some(regex) = cache.regexes.get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(pattern)
+            .map_err(|error| InterpreterError::ValueError(format!("bad regexp: {}", error)))?;
+        if cache.regexes.len() >= CACHE_CAPACITY {
+            cache.regexes.clear();
+        }
+        let regex = Rc::new(regex);
+        cache.regexes.insert(pattern.into(), regex.clone());
+        Ok(regex)
+    })
+}
+
+/// A `regexp` value, or a pattern string to compile.
+fn regex(value: &Value) -> Result<Rc<Regex>> {
+    match value {
+        Value::Regexp(regex) => Ok(regex.clone()),
+        Value::Str(pattern) => compile(&pattern.borrow()),
+        value => Err(not_a("regexp", value)),
+    }
+}
+
+fn string(value: &Value) -> Result<String> {
+    match value {
+        Value::Str(string) => Ok(string.borrow().clone()),
+        value => Err(not_a("string", value)),
+    }
+}
+
+fn char_index(string: &str, byte_offset: usize) -> Value {
+    Value::Int(string[..byte_offset].chars().count() as i128)
+}
+
+/// The whole match and every group, `#f` for groups that didn't take part.
+fn groups(captures: &Captures, group: impl Fn(regex::Match) -> Value) -> Value {
+    let groups = captures
+        .iter()
+        .map(|matched| matched.map_or(Value::Bool(false), &group))
+        .collect::<Vec<_>>();
+    Value::list(groups.into_iter())
+}
+
+/// Applies the regular expression procedures. Patterns are either `regexp`
+/// values or strings, and use the syntax of the `regex` crate, with `$1`
+/// for groups in replacements.
+pub fn apply_regexp_primitive(operation: Operation, arguments: &[Value]) -> Result<Value> {
+    match operation {
+        Operation::Regexp => Ok(Value::Regexp(regex(&arguments[0])?)),
+        Operation::IsRegexp => Ok(Value::Bool(matches!(arguments[0], Value::Regexp(_)))),
+        Operation::RegexpMatch => {
+            let string = string(&arguments[1])?;
+            Ok(match regex(&arguments[0])?.captures(&string) {
+                Some(captures) => groups(&captures, |matched| Value::string(matched.as_str())),
+                None => Value::Bool(false),
+            })
+        }
+        Operation::RegexpMatchPositions => {
+            let string = string(&arguments[1])?;
+            Ok(match regex(&arguments[0])?.captures(&string) {
+                Some(captures) => groups(&captures, |matched| Value::Cons {
+                    head: Rc::new(char_index(&string, matched.start())),
+                    tail: Rc::new(char_index(&string, matched.end())),
+                }),
+                None => Value::Bool(false),
+            })
+        }
+        Operation::RegexpReplace | Operation::RegexpReplaceAll => {
+            let regex = regex(&arguments[0])?;
+            let string = string(&arguments[1])?;
+            let replacement = self::string(&arguments[2])?;
+            let replaced = match operation {
+                Operation::RegexpReplace => regex.replace(&string, replacement.as_str()),
+                _ => regex.replace_all(&string, replacement.as_str()),
+            };
+            Ok(Value::string(replaced.into_owned()))
+        }
+        Operation::RegexpSplit => {
+            let string = string(&arguments[1])?;
+            let parts = regex(&arguments[0])?
+                .split(&string)
+                .map(Value::string)
+                .collect::<Vec<_>>();
+            Ok(Value::list(parts.into_iter()))
+        }
+        _ => Err(InterpreterError::RuntimeError(format!(
+            "{} is not a regexp primitive",
+            operation.name()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compile;
+    use std::rc::Rc;
+
+    #[test]
+    fn patterns_are_compiled_once() {
+        let first = compile("[a-z]+(\\d)").unwrap();
+        let second = compile("[a-z]+(\\d)").unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+        assert!(compile("(").is_err());
+    }
+}
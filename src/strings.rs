@@ -0,0 +1,190 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::error::{InterpreterError, Result};
+use crate::eval::{not_a, reserve, slice_bounds};
+use crate::value::{Operation, Value};
+
+fn string(value: &Value) -> Result<Rc<RefCell<String>>> {
+    match value {
+        Value::Str(string) => Ok(string.clone()),
+        value => Err(not_a("string", value)),
+    }
+}
+
+fn char(value: &Value) -> Result<char> {
+    match value {
+        Value::Char(char) => Ok(*char),
+        value => Err(not_a("character", value)),
+    }
+}
+
+/// Byte offset of the character at `index`, or of the end for the index
+/// after the last character. Strings are indexed by characters, not bytes.
+fn byte_offset(string: &str, index: usize) -> usize {
+    string
+        .char_indices()
+        .nth(index)
+        .map_or(string.len(), |(offset, _)| offset)
+}
+
+/// The characters between the optional start and end index `arguments`.
+fn slice<'a>(string: &'a str, arguments: &[Value]) -> Result<&'a str> {
+    let (start, end) = slice_bounds(arguments, string.chars().count())?;
+    Ok(&string[byte_offset(string, start)..byte_offset(string, end)])
+}
+
+fn char_index(string: &str, byte_offset: usize) -> Value {
+    Value::Int(string[..byte_offset].chars().count() as i128)
+}
+
+fn compare(arguments: &[Value], accepts: fn(Ordering) -> bool) -> Result<Value> {
+    let strings = arguments.iter().map(string).collect::<Result<Vec<_>>>()?;
+    Ok(Value::Bool(strings.windows(2).all(|pair| {
+        accepts(pair[0].borrow().as_str().cmp(pair[1].borrow().as_str()))
+    })))
+}
+
+fn chars(arguments: &[Value]) -> Result<String> {
+    arguments.iter().map(char).collect()
+}
+
+/// Applies the string procedures that don't call a procedure argument.
+pub fn apply_string_primitive(operation: Operation, arguments: &[Value]) -> Result<Value> {
+    match operation {
+        Operation::String => Ok(Value::string(chars(arguments)?)),
+        Operation::MakeString => {
+            let Value::Int(length) = arguments[0] else {
+                return Err(not_a("length", &arguments[0]));
+            };
+            let length = usize::try_from(length).map_err(|_| not_a("length", &arguments[0]))?;
+            let fill = arguments.get(1).map(char).transpose()?.unwrap_or(' ');
+            let mut chars = reserve(length)?;
+            chars.resize(length, fill);
+            Ok(Value::string(chars.into_iter().collect::<String>()))
+        }
+        Operation::StringLength => Ok(Value::Int(
+            string(&arguments[0])?.borrow().chars().count() as i128
+        )),
+        Operation::StringRef => {
+            let string = string(&arguments[0])?;
+            let string = string.borrow();
+            let (index, _) = slice_bounds(&arguments[1..], string.chars().count())?;
+            string.chars().nth(index).map(Value::Char).ok_or_else(|| {
+                InterpreterError::ValueError(format!("index {} out of range", index))
+            })
+        }
+        Operation::Substring | Operation::StringCopy => {
+            let string = string(&arguments[0])?;
+            let string = string.borrow();
+            Ok(Value::string(slice(&string, &arguments[1..])?))
+        }
+        Operation::StringAppend => {
+            let mut appended = String::new();
+            for argument in arguments {
+                appended.push_str(&string(argument)?.borrow());
+            }
+            Ok(Value::string(appended))
+        }
+        Operation::StringToList => {
+            let string = string(&arguments[0])?;
+            let string = string.borrow();
+            let chars = slice(&string, &arguments[1..])?.chars().map(Value::Char);
+            Ok(Value::list(chars.collect::<Vec<_>>().into_iter()))
+        }
+        Operation::ListToString => Ok(Value::string(chars(&arguments[0].to_vec()?)?)),
+        Operation::StringUpcase => Ok(Value::string(
+            string(&arguments[0])?.borrow().to_uppercase(),
+        )),
+        Operation::StringDowncase => Ok(Value::string(
+            string(&arguments[0])?.borrow().to_lowercase(),
+        )),
+        Operation::StringIndex => {
+            let string = string(&arguments[0])?;
+            let string = string.borrow();
+            let char = char(&arguments[1])?;
+            Ok(string
+                .chars()
+                .position(|other| other == char)
+                .map_or(Value::Bool(false), |index| Value::Int(index as i128)))
+        }
+        Operation::StringContains => {
+            let string = string(&arguments[0])?;
+            let string = string.borrow();
+            let pattern = self::string(&arguments[1])?;
+            let found = string.find(pattern.borrow().as_str());
+            Ok(found.map_or(Value::Bool(false), |offset| char_index(&string, offset)))
+        }
+        Operation::StringSplit => {
+            let string = string(&arguments[0])?;
+            let string = string.borrow();
+            let parts: Vec<Value> = match arguments.get(1) {
+                None => string.split_whitespace().map(Value::string).collect(),
+                Some(Value::Char(separator)) => {
+                    string.split(*separator).map(Value::string).collect()
+                }
+                Some(separator) => {
+                    let separator = self::string(separator)?;
+                    let separator = separator.borrow();
+                    string
+                        .split(separator.as_str())
+                        .map(Value::string)
+                        .collect()
+                }
+            };
+            Ok(Value::list(parts.into_iter()))
+        }
+        Operation::StringJoin => {
+            let parts = arguments[0]
+                .to_vec()?
+                .iter()
+                .map(|part| string(part).map(|part| part.borrow().clone()))
+                .collect::<Result<Vec<_>>>()?;
+            let delimiter = match arguments.get(1) {
+                Some(delimiter) => string(delimiter)?.borrow().clone(),
+                None => " ".into(),
+            };
+            Ok(Value::string(parts.join(&delimiter)))
+        }
+        Operation::StringTrim => Ok(Value::string(string(&arguments[0])?.borrow().trim_start())),
+        Operation::StringTrimRight => Ok(Value::string(string(&arguments[0])?.borrow().trim_end())),
+        Operation::StringTrimBoth => Ok(Value::string(string(&arguments[0])?.borrow().trim())),
+        Operation::StringEq => compare(arguments, Ordering::is_eq),
+        Operation::StringLess => compare(arguments, Ordering::is_lt),
+        Operation::StringGreater => compare(arguments, Ordering::is_gt),
+        Operation::StringLessOrEqual => compare(arguments, Ordering::is_le),
+        Operation::StringGreaterOrEqual => compare(arguments, Ordering::is_ge),
+        Operation::StringSet => {
+            let string = string(&arguments[0])?;
+            let char = char(&arguments[2])?;
+            let mut string = string.borrow_mut();
+            let length = string.chars().count();
+            let (index, _) = slice_bounds(&arguments[1..2], length)?;
+            if index == length {
+                return Err(InterpreterError::ValueError(format!(
+                    "index {} out of range",
+                    index
+                )));
+            }
+            let offset = byte_offset(&string, index);
+            let width = string[offset..].chars().next().map_or(0, char::len_utf8);
+            string.replace_range(offset..offset + width, char.encode_utf8(&mut [0; 4]));
+            Ok(Value::Nil)
+        }
+        Operation::StringFill => {
+            let string = string(&arguments[0])?;
+            let char = char(&arguments[1])?;
+            let mut string = string.borrow_mut();
+            let (start, end) = slice_bounds(&arguments[2..], string.chars().count())?;
+            let (start, end) = (byte_offset(&string, start), byte_offset(&string, end));
+            let filled = string[start..end].chars().map(|_| char).collect::<String>();
+            string.replace_range(start..end, &filled);
+            Ok(Value::Nil)
+        }
+        _ => Err(InterpreterError::RuntimeError(format!(
+            "{} is not a string primitive",
+            operation.name()
+        ))),
+    }
+}
@@ -0,0 +1,222 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::rc::Rc;
+
+use crate::value::OPERATION_NAMES;
+
+/// Handle to an interned name. Two symbols with the same name are the same
+/// handle, so comparing and hashing them never looks at the characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Map keyed by symbols that hashes the handle directly.
+pub type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
+
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 << 8) | *byte as u64;
+        }
+    }
+
+    fn write_u32(&mut self, id: u32) {
+        // spread the handle over all bits, the hash table uses the high ones
+        self.0 = (id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+#[derive(Default)]
+struct SymbolTable {
+    names: Vec<Rc<str>>,
+    interned: HashMap<Rc<str>, Symbol>,
+    origins: SymbolMap<Symbol>,
+    /// The fresh names of local variables, by the symbol they are renamed
+    /// from, see `Symbol::renamed`.
+    renames: SymbolMap<Vec<Symbol>>,
+    /// Entries of freed aliases, reused for the next ones.
+    free_aliases: Vec<Symbol>,
+    gensym_counter: usize,
+}
+
+thread_local! {
+    static SYMBOL_TABLE: RefCell<SymbolTable> = RefCell::new(SymbolTable::new());
+}
+
+impl SymbolTable {
+    /// The operation names are interned first and in order, so the symbol
+    /// of an operation is also its index in `OPERATION_NAMES`.
+    fn new() -> Self {
+        let mut table = SymbolTable::default();
+        for (name, _) in OPERATION_NAMES {
+            table.intern(name);
+        }
+        table
+    }
+
+    fn add(&mut self, name: Rc<str>) -> Symbol {
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name);
+        symbol
+    }
+
+    fn gensym(&mut self, prefix: &str) -> Symbol {
+        self.gensym_counter += 1;
+        let name = format!("{}{}", prefix, self.gensym_counter);
+        self.add(name.into())
+    }
+
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.interned.get(name) {
+            return *symbol;
+        }
+        let name: Rc<str> = name.into();
+        let symbol = self.add(name.clone());
+        self.interned.insert(name, symbol);
+        symbol
+    }
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        SYMBOL_TABLE.with(|table| table.borrow_mut().intern(name))
+    }
+
+    /// Creates a fresh symbol that is never returned by `intern`, even if
+    /// another symbol with the same name exists.
+    pub fn gensym(prefix: &str) -> Symbol {
+        SYMBOL_TABLE.with(|table| table.borrow_mut().gensym(prefix))
+    }
+
+    /// The `index`th fresh name for local variables named `self`. Each
+    /// expansion numbers its variables from zero and so reuses the names of
+    /// the ones before it, which can't meet it in any environment.
+    pub fn renamed(self, index: usize) -> Symbol {
+        SYMBOL_TABLE.with(|table| {
+            let mut table = table.borrow_mut();
+            while table.renames.get(&self).map_or(0, Vec::len) <= index {
+                let prefix = format!("{}.", table.names[self.index()]);
+                let renamed = table.gensym(&prefix);
+                table.renames.entry(self).or_default().push(renamed);
+            }
+            table.renames[&self][index]
+        })
+    }
+
+    /// Creates a fresh symbol with the same name that remembers `self` as
+    /// its origin. Macro expansion renames the identifiers a macro inserts
+    /// to aliases, so they can't be confused with those at the use site.
+    pub fn alias(self) -> Symbol {
+        SYMBOL_TABLE.with(|table| {
+            let mut table = table.borrow_mut();
+            let name = table.names[self.index()].clone();
+            let alias = match table.free_aliases.pop() {
+                Some(alias) => {
+                    table.names[alias.index()] = name;
+                    alias
+                }
+                None => table.add(name),
+            };
+            table.origins.insert(alias, self);
+            alias
+        })
+    }
+
+    /// Gives the entry of an alias back to the table for the next alias, so
+    /// that expansions don't keep adding symbols. Nothing may hold on to the
+    /// alias afterwards.
+    pub fn free(self) {
+        SYMBOL_TABLE.with(|table| {
+            let mut table = table.borrow_mut();
+            if table.origins.remove(&self).is_some() {
+                table.free_aliases.push(self);
+            }
+        })
+    }
+
+    pub fn origin(self) -> Option<Symbol> {
+        SYMBOL_TABLE.with(|table| table.borrow().origins.get(&self).copied())
+    }
+
+    /// Follows aliases back to the symbol that was written in the source.
+    pub fn strip(self) -> Symbol {
+        let mut symbol = self;
+        while let Some(origin) = symbol.origin() {
+            symbol = origin;
+        }
+        symbol
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn name(&self) -> Rc<str> {
+        SYMBOL_TABLE.with(|table| table.borrow().names[self.0 as usize].clone())
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbol;
+
+    #[test]
+    fn interned_symbols_are_shared() {
+        assert_eq!(Symbol::intern("lambda"), Symbol::intern("lambda"));
+        assert_ne!(Symbol::intern("lambda"), Symbol::intern("define"));
+        assert_eq!(Symbol::intern("lambda").name().as_ref(), "lambda");
+    }
+
+    #[test]
+    fn gensyms_are_uninterned() {
+        let generated = Symbol::gensym("g");
+        assert_ne!(Symbol::intern(&generated.name()), generated);
+        assert_ne!(Symbol::gensym("g"), generated);
+    }
+
+    #[test]
+    fn aliases_remember_their_origin() {
+        let symbol = Symbol::intern("x");
+        let alias = symbol.alias().alias();
+        assert_ne!(alias, symbol);
+        assert_eq!(alias.name(), symbol.name());
+        assert_eq!(alias.strip(), symbol);
+        assert_eq!(symbol.origin(), None);
+    }
+
+    #[test]
+    fn freed_aliases_are_reused() {
+        let symbol = Symbol::intern("x");
+        let alias = symbol.alias();
+        alias.free();
+        let reused = Symbol::intern("y").alias();
+        assert_eq!(reused, alias);
+        assert_eq!(reused.name().as_ref(), "y");
+        assert_eq!(reused.strip(), Symbol::intern("y"));
+        symbol.free();
+        assert_eq!(symbol.name().as_ref(), "x");
+    }
+
+    #[test]
+    fn renames_are_numbered() {
+        let symbol = Symbol::intern("x");
+        assert_eq!(symbol.renamed(1), symbol.renamed(1));
+        assert_ne!(symbol.renamed(0), symbol.renamed(1));
+        assert_ne!(symbol.renamed(0), symbol);
+        assert_ne!(Symbol::intern("y").renamed(0), symbol.renamed(0));
+    }
+}
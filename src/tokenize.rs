@@ -1,4 +1,6 @@
 use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::str::Chars;
 
 #[derive(Debug)]
 pub enum Token {
@@ -10,24 +12,74 @@ pub enum Token {
     Symbol(String),
 }
 
+fn atom(token: &str) -> Token {
+    if let Ok(int) = token.parse::<i128>() {
+        Token::Int(int)
+    } else if let Ok(float) = token.parse::<f64>() {
+        Token::Float(float)
+    } else {
+        Token::Symbol(token.to_string())
+    }
+}
+
+/// Reads a string literal after its opening quote. `\"`, `\\`, `\n`, `\t`
+/// and `\r` are escapes, any other backslash is kept as it is.
+fn string_literal(chars: &mut Peekable<Chars>) -> String {
+    let mut string = String::new();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' => break,
+            '\\' => match chars.peek() {
+                Some(escaped @ ('"' | '\\')) => {
+                    string.push(*escaped);
+                    chars.next();
+                }
+                Some('n') => {
+                    string.push('\n');
+                    chars.next();
+                }
+                Some('t') => {
+                    string.push('\t');
+                    chars.next();
+                }
+                Some('r') => {
+                    string.push('\r');
+                    chars.next();
+                }
+                _ => string.push('\\'),
+            },
+            char => string.push(char),
+        }
+    }
+    string
+}
+
+fn ends_atom(char: char) -> bool {
+    char.is_whitespace() || matches!(char, '(' | ')' | '"')
+}
+
 pub fn tokenize(code: &str) -> VecDeque<Token> {
-    code.replace('(', " ( ")
-        .replace(')', " ) ")
-        .split_whitespace()
-        .map(|token| match token {
-            "(" => Token::LeftParanthesis,
-            ")" => Token::RightParanthesis,
-            _ => {
-                if let Ok(int) = token.parse::<i128>() {
-                    Token::Int(int)
-                } else if let Ok(float) = token.parse::<f64>() {
-                    Token::Float(float)
-                } else if token.starts_with('"') & token.ends_with('"') {
-                    Token::String(token.to_string())
-                } else {
-                    Token::Symbol(token.to_string())
+    let mut tokens = VecDeque::new();
+    let mut chars = code.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '(' => tokens.push_back(Token::LeftParanthesis),
+            ')' => tokens.push_back(Token::RightParanthesis),
+            '"' => tokens.push_back(Token::String(string_literal(&mut chars))),
+            char if char.is_whitespace() => {}
+            char => {
+                let mut token = String::from(char);
+                // the character of `#\(` or `#\ ` would end any other atom
+                if char == '#' && chars.peek() == Some(&'\\') {
+                    token.extend(chars.next());
+                    token.extend(chars.next());
+                }
+                while let Some(next) = chars.next_if(|next| !ends_atom(*next)) {
+                    token.push(next);
                 }
+                tokens.push_back(atom(&token));
             }
-        })
-        .collect()
+        }
+    }
+    tokens
 }
@@ -0,0 +1,1087 @@
+use std::cell::{Cell, RefCell};
+use std::fmt::Display;
+use std::rc::Rc;
+
+use regex::Regex;
+
+use crate::env::Env;
+use crate::error::{InterpreterError, Result};
+use crate::eval::Continuation;
+use crate::expand::Macro;
+use crate::parse::{Datum, MAX_DEPTH};
+use crate::port::Port;
+use crate::symbol::Symbol;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Operation {
+    Add,
+    Substract,
+    Divide,
+    Multiply,
+    Pow,
+    Exp,
+    Car,
+    Cdr,
+    Cons,
+    List,
+    Begin,
+    Module,
+    Cond,
+    If,
+    Eq,
+    Smaller,
+    Greater,
+    SmallerOrEqual,
+    GreaterOrEqual,
+    Define,
+    Set,
+    Lambda,
+    Quote,
+    Eval,
+    Let,
+    StringToSymbol,
+    SymbolToString,
+    SymbolEq,
+    Gensym,
+    CallCC,
+    CallEC,
+    DynamicWind,
+    Error,
+    Raise,
+    RaiseContinuable,
+    WithExceptionHandler,
+    Guard,
+    IsErrorObject,
+    ErrorObjectMessage,
+    ErrorObjectIrritants,
+    DefineSyntax,
+    LetSyntax,
+    LetrecSyntax,
+    SyntaxRules,
+    ErMacroTransformer,
+    MacroExpand,
+    MacroExpand1,
+    CaseLambda,
+    Apply,
+    Map,
+    ForEach,
+    VectorMap,
+    StringMap,
+    FoldLeft,
+    FoldRight,
+    Reduce,
+    Vector,
+    VectorRef,
+    VectorLength,
+    VectorToList,
+    ListToVector,
+    CharUpcase,
+    Append,
+    Reverse,
+    Length,
+    ListTail,
+    ListRef,
+    ListCopy,
+    LastPair,
+    Filter,
+    Remove,
+    Partition,
+    Delete,
+    DeleteDuplicates,
+    Find,
+    FindTail,
+    Any,
+    Every,
+    Count,
+    Iota,
+    Take,
+    Drop,
+    Assoc,
+    Assq,
+    Assv,
+    Member,
+    Memq,
+    Memv,
+    AppendMap,
+    FilterMap,
+    ListSort,
+    VectorSort,
+    VectorSortInPlace,
+    ListMerge,
+    IsSorted,
+    String,
+    MakeString,
+    StringLength,
+    StringRef,
+    Substring,
+    StringAppend,
+    StringCopy,
+    StringToList,
+    ListToString,
+    StringUpcase,
+    StringDowncase,
+    StringIndex,
+    StringContains,
+    StringSplit,
+    StringJoin,
+    StringTrim,
+    StringTrimRight,
+    StringTrimBoth,
+    StringEq,
+    StringLess,
+    StringGreater,
+    StringLessOrEqual,
+    StringGreaterOrEqual,
+    StringSet,
+    StringFill,
+    Regexp,
+    IsRegexp,
+    RegexpMatch,
+    RegexpMatchPositions,
+    RegexpReplace,
+    RegexpReplaceAll,
+    RegexpSplit,
+    OpenOutputString,
+    OpenInputString,
+    GetOutputString,
+    WithOutputToString,
+    CallWithOutputString,
+    Display,
+    Write,
+    Newline,
+    ReadChar,
+    PeekChar,
+    ReadLine,
+    EofObject,
+    IsEofObject,
+    WriteShared,
+    WriteSimple,
+    WriteChar,
+    WriteString,
+    FlushOutputPort,
+    CurrentOutputPort,
+    WithOutputToPort,
+    ReadString,
+    IsCharReady,
+    OpenInputFile,
+    OpenOutputFile,
+    CallWithInputFile,
+    CallWithOutputFile,
+    WithOutputToFile,
+    ClosePort,
+    Bytevector,
+    IsBytevector,
+    MakeBytevector,
+    BytevectorLength,
+    BytevectorU8Ref,
+    BytevectorU8Set,
+    BytevectorCopy,
+    BytevectorAppend,
+    Utf8ToString,
+    StringToUtf8,
+    BytevectorU16Ref,
+    BytevectorU16Set,
+    BytevectorS16Ref,
+    BytevectorS16Set,
+    BytevectorU32Ref,
+    BytevectorU32Set,
+    BytevectorS32Ref,
+    BytevectorS32Set,
+    BytevectorU64Ref,
+    BytevectorU64Set,
+    BytevectorS64Ref,
+    BytevectorS64Set,
+    OpenInputBytevector,
+    OpenOutputBytevector,
+    GetOutputBytevector,
+    OpenBinaryInputFile,
+    OpenBinaryOutputFile,
+    ReadU8,
+    PeekU8,
+    IsU8Ready,
+    ReadBytevector,
+    WriteU8,
+    WriteBytevector,
+    Read,
+    IsReadError,
+    IsFileError,
+    InteractionEnvironment,
+    SchemeReportEnvironment,
+    NullEnvironment,
+    RestrictedEnvironment,
+    IsEnvironment,
+    Format,
+}
+
+pub const OPERATION_NAMES: [(&str, Operation); 199] = [
+    ("+", Operation::Add),
+    ("-", Operation::Substract),
+    ("*", Operation::Multiply),
+    ("/", Operation::Divide),
+    ("pow", Operation::Pow),
+    ("exp", Operation::Exp),
+    ("car", Operation::Car),
+    ("cdr", Operation::Cdr),
+    ("cons", Operation::Cons),
+    ("list", Operation::List),
+    ("begin", Operation::Begin),
+    ("module", Operation::Module),
+    ("cond", Operation::Cond),
+    ("if", Operation::If),
+    ("=", Operation::Eq),
+    ("<", Operation::Smaller),
+    (">", Operation::Greater),
+    ("<=", Operation::SmallerOrEqual),
+    (">=", Operation::GreaterOrEqual),
+    ("define", Operation::Define),
+    ("set!", Operation::Set),
+    ("lambda", Operation::Lambda),
+    ("quote", Operation::Quote),
+    ("eval", Operation::Eval),
+    ("let", Operation::Let),
+    ("string->symbol", Operation::StringToSymbol),
+    ("symbol->string", Operation::SymbolToString),
+    ("symbol=?", Operation::SymbolEq),
+    ("gensym", Operation::Gensym),
+    ("call-with-current-continuation", Operation::CallCC),
+    ("call/cc", Operation::CallCC),
+    ("call-with-escape-continuation", Operation::CallEC),
+    ("call/ec", Operation::CallEC),
+    ("dynamic-wind", Operation::DynamicWind),
+    ("error", Operation::Error),
+    ("raise", Operation::Raise),
+    ("raise-continuable", Operation::RaiseContinuable),
+    ("with-exception-handler", Operation::WithExceptionHandler),
+    ("guard", Operation::Guard),
+    ("error-object?", Operation::IsErrorObject),
+    ("error-object-message", Operation::ErrorObjectMessage),
+    ("error-object-irritants", Operation::ErrorObjectIrritants),
+    ("define-syntax", Operation::DefineSyntax),
+    ("let-syntax", Operation::LetSyntax),
+    ("letrec-syntax", Operation::LetrecSyntax),
+    ("syntax-rules", Operation::SyntaxRules),
+    ("er-macro-transformer", Operation::ErMacroTransformer),
+    ("macroexpand", Operation::MacroExpand),
+    ("macroexpand-1", Operation::MacroExpand1),
+    ("case-lambda", Operation::CaseLambda),
+    ("apply", Operation::Apply),
+    ("map", Operation::Map),
+    ("for-each", Operation::ForEach),
+    ("vector-map", Operation::VectorMap),
+    ("string-map", Operation::StringMap),
+    ("fold-left", Operation::FoldLeft),
+    ("fold-right", Operation::FoldRight),
+    ("reduce", Operation::Reduce),
+    ("vector", Operation::Vector),
+    ("vector-ref", Operation::VectorRef),
+    ("vector-length", Operation::VectorLength),
+    ("vector->list", Operation::VectorToList),
+    ("list->vector", Operation::ListToVector),
+    ("char-upcase", Operation::CharUpcase),
+    ("append", Operation::Append),
+    ("reverse", Operation::Reverse),
+    ("length", Operation::Length),
+    ("list-tail", Operation::ListTail),
+    ("list-ref", Operation::ListRef),
+    ("list-copy", Operation::ListCopy),
+    ("last-pair", Operation::LastPair),
+    ("filter", Operation::Filter),
+    ("remove", Operation::Remove),
+    ("partition", Operation::Partition),
+    ("delete", Operation::Delete),
+    ("delete-duplicates", Operation::DeleteDuplicates),
+    ("find", Operation::Find),
+    ("find-tail", Operation::FindTail),
+    ("any", Operation::Any),
+    ("every", Operation::Every),
+    ("count", Operation::Count),
+    ("iota", Operation::Iota),
+    ("take", Operation::Take),
+    ("drop", Operation::Drop),
+    ("assoc", Operation::Assoc),
+    ("assq", Operation::Assq),
+    ("assv", Operation::Assv),
+    ("member", Operation::Member),
+    ("memq", Operation::Memq),
+    ("memv", Operation::Memv),
+    ("append-map", Operation::AppendMap),
+    ("filter-map", Operation::FilterMap),
+    ("list-sort", Operation::ListSort),
+    ("vector-sort", Operation::VectorSort),
+    ("vector-sort!", Operation::VectorSortInPlace),
+    ("list-merge", Operation::ListMerge),
+    ("sorted?", Operation::IsSorted),
+    ("string", Operation::String),
+    ("make-string", Operation::MakeString),
+    ("string-length", Operation::StringLength),
+    ("string-ref", Operation::StringRef),
+    ("substring", Operation::Substring),
+    ("string-append", Operation::StringAppend),
+    ("string-copy", Operation::StringCopy),
+    ("string->list", Operation::StringToList),
+    ("list->string", Operation::ListToString),
+    ("string-upcase", Operation::StringUpcase),
+    ("string-downcase", Operation::StringDowncase),
+    ("string-index", Operation::StringIndex),
+    ("string-contains", Operation::StringContains),
+    ("string-split", Operation::StringSplit),
+    ("string-join", Operation::StringJoin),
+    ("string-trim", Operation::StringTrim),
+    ("string-trim-right", Operation::StringTrimRight),
+    ("string-trim-both", Operation::StringTrimBoth),
+    ("string=?", Operation::StringEq),
+    ("string<?", Operation::StringLess),
+    ("string>?", Operation::StringGreater),
+    ("string<=?", Operation::StringLessOrEqual),
+    ("string>=?", Operation::StringGreaterOrEqual),
+    ("string-set!", Operation::StringSet),
+    ("string-fill!", Operation::StringFill),
+    ("regexp", Operation::Regexp),
+    ("regexp?", Operation::IsRegexp),
+    ("regexp-match", Operation::RegexpMatch),
+    ("regexp-match-positions", Operation::RegexpMatchPositions),
+    ("regexp-replace", Operation::RegexpReplace),
+    ("regexp-replace-all", Operation::RegexpReplaceAll),
+    ("regexp-split", Operation::RegexpSplit),
+    ("open-output-string", Operation::OpenOutputString),
+    ("open-input-string", Operation::OpenInputString),
+    ("get-output-string", Operation::GetOutputString),
+    ("with-output-to-string", Operation::WithOutputToString),
+    ("call-with-output-string", Operation::CallWithOutputString),
+    ("display", Operation::Display),
+    ("write", Operation::Write),
+    ("newline", Operation::Newline),
+    ("read-char", Operation::ReadChar),
+    ("peek-char", Operation::PeekChar),
+    ("read-line", Operation::ReadLine),
+    ("eof-object", Operation::EofObject),
+    ("eof-object?", Operation::IsEofObject),
+    ("write-shared", Operation::WriteShared),
+    ("write-simple", Operation::WriteSimple),
+    ("write-char", Operation::WriteChar),
+    ("write-string", Operation::WriteString),
+    ("flush-output-port", Operation::FlushOutputPort),
+    ("current-output-port", Operation::CurrentOutputPort),
+    ("with-output-to-port", Operation::WithOutputToPort),
+    ("read-string", Operation::ReadString),
+    ("char-ready?", Operation::IsCharReady),
+    ("open-input-file", Operation::OpenInputFile),
+    ("open-output-file", Operation::OpenOutputFile),
+    ("call-with-input-file", Operation::CallWithInputFile),
+    ("call-with-output-file", Operation::CallWithOutputFile),
+    ("with-output-to-file", Operation::WithOutputToFile),
+    ("close-port", Operation::ClosePort),
+    ("bytevector", Operation::Bytevector),
+    ("bytevector?", Operation::IsBytevector),
+    ("make-bytevector", Operation::MakeBytevector),
+    ("bytevector-length", Operation::BytevectorLength),
+    ("bytevector-u8-ref", Operation::BytevectorU8Ref),
+    ("bytevector-u8-set!", Operation::BytevectorU8Set),
+    ("bytevector-copy", Operation::BytevectorCopy),
+    ("bytevector-append", Operation::BytevectorAppend),
+    ("utf8->string", Operation::Utf8ToString),
+    ("string->utf8", Operation::StringToUtf8),
+    ("bytevector-u16-ref", Operation::BytevectorU16Ref),
+    ("bytevector-u16-set!", Operation::BytevectorU16Set),
+    ("bytevector-s16-ref", Operation::BytevectorS16Ref),
+    ("bytevector-s16-set!", Operation::BytevectorS16Set),
+    ("bytevector-u32-ref", Operation::BytevectorU32Ref),
+    ("bytevector-u32-set!", Operation::BytevectorU32Set),
+    ("bytevector-s32-ref", Operation::BytevectorS32Ref),
+    ("bytevector-s32-set!", Operation::BytevectorS32Set),
+    ("bytevector-u64-ref", Operation::BytevectorU64Ref),
+    ("bytevector-u64-set!", Operation::BytevectorU64Set),
+    ("bytevector-s64-ref", Operation::BytevectorS64Ref),
+    ("bytevector-s64-set!", Operation::BytevectorS64Set),
+    ("open-input-bytevector", Operation::OpenInputBytevector),
+    ("open-output-bytevector", Operation::OpenOutputBytevector),
+    ("get-output-bytevector", Operation::GetOutputBytevector),
+    ("open-binary-input-file", Operation::OpenBinaryInputFile),
+    ("open-binary-output-file", Operation::OpenBinaryOutputFile),
+    ("read-u8", Operation::ReadU8),
+    ("peek-u8", Operation::PeekU8),
+    ("u8-ready?", Operation::IsU8Ready),
+    ("read-bytevector", Operation::ReadBytevector),
+    ("write-u8", Operation::WriteU8),
+    ("write-bytevector", Operation::WriteBytevector),
+    ("read", Operation::Read),
+    ("read-error?", Operation::IsReadError),
+    ("file-error?", Operation::IsFileError),
+    ("interaction-environment", Operation::InteractionEnvironment),
+    (
+        "scheme-report-environment",
+        Operation::SchemeReportEnvironment,
+    ),
+    ("null-environment", Operation::NullEnvironment),
+    ("restricted-environment", Operation::RestrictedEnvironment),
+    ("environment?", Operation::IsEnvironment),
+    ("format", Operation::Format),
+];
+
+impl Operation {
+    pub fn get(symbol: Symbol) -> Option<Operation> {
+        OPERATION_NAMES
+            .get(symbol.index())
+            .map(|(_, operation)| *operation)
+    }
+
+    pub fn name(self) -> &'static str {
+        OPERATION_NAMES
+            .iter()
+            .find(|(_, operation)| *operation == self)
+            .map_or("?", |(name, _)| name)
+    }
+
+    /// Smallest and largest number of arguments the procedure takes.
+    pub fn arity(self) -> Arity {
+        match self {
+            Operation::Add | Operation::Substract | Operation::Multiply | Operation::Divide => {
+                (1, None)
+            }
+            Operation::Eq
+            | Operation::Smaller
+            | Operation::Greater
+            | Operation::SmallerOrEqual
+            | Operation::GreaterOrEqual
+            | Operation::SymbolEq
+            | Operation::Error => (1, None),
+            Operation::List | Operation::Vector | Operation::Append => (0, None),
+            Operation::Format => (1, None),
+            Operation::Any | Operation::Every | Operation::Count => (2, None),
+            Operation::AppendMap | Operation::FilterMap => (2, None),
+            Operation::Delete | Operation::Assoc | Operation::Member => (2, Some(3)),
+            Operation::DeleteDuplicates => (1, Some(2)),
+            Operation::ListSort | Operation::IsSorted => (2, Some(2)),
+            Operation::VectorSort | Operation::VectorSortInPlace => (2, Some(4)),
+            Operation::ListMerge => (3, Some(3)),
+            Operation::String | Operation::StringAppend => (0, None),
+            Operation::StringEq
+            | Operation::StringLess
+            | Operation::StringGreater
+            | Operation::StringLessOrEqual
+            | Operation::StringGreaterOrEqual => (1, None),
+            Operation::MakeString | Operation::StringSplit | Operation::StringJoin => (1, Some(2)),
+            Operation::StringCopy | Operation::StringToList => (1, Some(3)),
+            Operation::Substring => (2, Some(3)),
+            Operation::StringRef | Operation::StringIndex | Operation::StringContains => {
+                (2, Some(2))
+            }
+            Operation::StringFill => (2, Some(4)),
+            Operation::StringSet => (3, Some(3)),
+            Operation::Regexp | Operation::IsRegexp => (1, Some(1)),
+            Operation::RegexpMatch | Operation::RegexpMatchPositions | Operation::RegexpSplit => {
+                (2, Some(2))
+            }
+            Operation::RegexpReplace | Operation::RegexpReplaceAll => (3, Some(3)),
+            Operation::OpenOutputString
+            | Operation::EofObject
+            | Operation::CurrentOutputPort
+            | Operation::InteractionEnvironment => (0, Some(0)),
+            Operation::OpenInputString
+            | Operation::GetOutputString
+            | Operation::WithOutputToString
+            | Operation::CallWithOutputString
+            | Operation::IsEofObject => (1, Some(1)),
+            Operation::Display
+            | Operation::Write
+            | Operation::WriteShared
+            | Operation::WriteSimple
+            | Operation::WriteChar => (1, Some(2)),
+            Operation::WriteString => (1, Some(4)),
+            Operation::ReadString | Operation::RestrictedEnvironment | Operation::Eval => {
+                (1, Some(2))
+            }
+            Operation::Bytevector | Operation::BytevectorAppend => (0, None),
+            Operation::IsBytevector
+            | Operation::BytevectorLength
+            | Operation::OpenInputBytevector
+            | Operation::GetOutputBytevector
+            | Operation::OpenBinaryInputFile
+            | Operation::OpenBinaryOutputFile => (1, Some(1)),
+            Operation::OpenOutputBytevector => (0, Some(0)),
+            Operation::MakeBytevector | Operation::ReadBytevector | Operation::WriteU8 => {
+                (1, Some(2))
+            }
+            Operation::BytevectorCopy | Operation::Utf8ToString | Operation::StringToUtf8 => {
+                (1, Some(3))
+            }
+            Operation::WriteBytevector => (1, Some(4)),
+            Operation::ReadU8 | Operation::PeekU8 | Operation::IsU8Ready => (0, Some(1)),
+            Operation::BytevectorU8Ref => (2, Some(2)),
+            Operation::BytevectorU8Set
+            | Operation::BytevectorU16Ref
+            | Operation::BytevectorS16Ref
+            | Operation::BytevectorU32Ref
+            | Operation::BytevectorS32Ref
+            | Operation::BytevectorU64Ref
+            | Operation::BytevectorS64Ref => (3, Some(3)),
+            Operation::BytevectorU16Set
+            | Operation::BytevectorS16Set
+            | Operation::BytevectorU32Set
+            | Operation::BytevectorS32Set
+            | Operation::BytevectorU64Set
+            | Operation::BytevectorS64Set => (4, Some(4)),
+            Operation::OpenInputFile | Operation::OpenOutputFile | Operation::ClosePort => {
+                (1, Some(1))
+            }
+            Operation::WithOutputToPort
+            | Operation::CallWithInputFile
+            | Operation::CallWithOutputFile
+            | Operation::WithOutputToFile => (2, Some(2)),
+            Operation::Newline
+            | Operation::FlushOutputPort
+            | Operation::IsCharReady
+            | Operation::Read
+            | Operation::ReadChar
+            | Operation::PeekChar
+            | Operation::ReadLine => (0, Some(1)),
+            Operation::StringLength
+            | Operation::ListToString
+            | Operation::StringUpcase
+            | Operation::StringDowncase
+            | Operation::StringTrim
+            | Operation::StringTrimRight
+            | Operation::StringTrimBoth => (1, Some(1)),
+            Operation::Iota => (1, Some(3)),
+            Operation::ListTail
+            | Operation::ListRef
+            | Operation::Filter
+            | Operation::Remove
+            | Operation::Partition
+            | Operation::Find
+            | Operation::FindTail
+            | Operation::Take
+            | Operation::Drop
+            | Operation::Assq
+            | Operation::Assv
+            | Operation::Memq
+            | Operation::Memv => (2, Some(2)),
+            Operation::Apply
+            | Operation::Map
+            | Operation::ForEach
+            | Operation::VectorMap
+            | Operation::StringMap => (2, None),
+            Operation::FoldLeft | Operation::FoldRight => (3, None),
+            Operation::Reduce => (3, Some(3)),
+            Operation::Gensym => (0, Some(1)),
+            Operation::Exp
+            | Operation::Car
+            | Operation::Cdr
+            | Operation::StringToSymbol
+            | Operation::SymbolToString
+            | Operation::CallCC
+            | Operation::CallEC
+            | Operation::Raise
+            | Operation::RaiseContinuable
+            | Operation::IsErrorObject
+            | Operation::SchemeReportEnvironment
+            | Operation::NullEnvironment
+            | Operation::IsEnvironment
+            | Operation::IsReadError
+            | Operation::IsFileError
+            | Operation::ErrorObjectMessage
+            | Operation::ErrorObjectIrritants
+            | Operation::VectorLength
+            | Operation::VectorToList
+            | Operation::ListToVector
+            | Operation::CharUpcase
+            | Operation::Reverse
+            | Operation::Length
+            | Operation::ListCopy
+            | Operation::LastPair => (1, Some(1)),
+            Operation::Pow
+            | Operation::Cons
+            | Operation::WithExceptionHandler
+            | Operation::VectorRef => (2, Some(2)),
+            Operation::DynamicWind => (3, Some(3)),
+            Operation::Begin
+            | Operation::Module
+            | Operation::Cond
+            | Operation::If
+            | Operation::Define
+            | Operation::Set
+            | Operation::Lambda
+            | Operation::Quote
+            | Operation::Let
+            | Operation::Guard
+            | Operation::DefineSyntax
+            | Operation::LetSyntax
+            | Operation::LetrecSyntax
+            | Operation::SyntaxRules
+            | Operation::ErMacroTransformer
+            | Operation::MacroExpand
+            | Operation::MacroExpand1
+            | Operation::CaseLambda => (0, None),
+        }
+    }
+
+    /// Special forms receive their arguments unevaluated, every other
+    /// operation is a procedure applied to evaluated arguments. The syntax
+    /// forms are handled by `expand` and never reach `eval`.
+    pub fn is_special_form(self) -> bool {
+        matches!(
+            self,
+            Operation::Begin
+                | Operation::Module
+                | Operation::Cond
+                | Operation::If
+                | Operation::Define
+                | Operation::Set
+                | Operation::Lambda
+                | Operation::Quote
+                | Operation::Let
+                | Operation::Guard
+                | Operation::DefineSyntax
+                | Operation::LetSyntax
+                | Operation::LetrecSyntax
+                | Operation::SyntaxRules
+                | Operation::ErMacroTransformer
+                | Operation::MacroExpand
+                | Operation::MacroExpand1
+                | Operation::CaseLambda
+        )
+    }
+}
+
+/// What `error` raises, and what handlers receive for errors of the
+/// interpreter itself.
+#[derive(Debug)]
+pub struct ErrorObject {
+    pub message: Rc<str>,
+    pub irritants: Value,
+    /// The interpreter error this object stands for.
+    pub cause: Option<InterpreterError>,
+}
+
+impl From<InterpreterError> for ErrorObject {
+    fn from(error: InterpreterError) -> Self {
+        ErrorObject {
+            message: error.message().into(),
+            irritants: Value::Nil,
+            cause: Some(error),
+        }
+    }
+}
+
+impl Display for ErrorObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        let mut irritants = &self.irritants;
+        while let Value::Cons { head, tail } = irritants {
+            write!(f, " {}", head)?;
+            irritants = tail;
+        }
+        Ok(())
+    }
+}
+
+pub type NativeFunction = dyn Fn(&[Value]) -> Result<Value>;
+
+/// A procedure implemented in Rust that closes over some state, like the
+/// `rename` procedure a macro transformer receives.
+pub struct Native {
+    pub name: &'static str,
+    pub function: Box<NativeFunction>,
+}
+
+impl std::fmt::Debug for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Native({})", self.name)
+    }
+}
+
+/// Parameter list of a lambda, like
+/// `(a #!optional (b 1) #!rest more #!key (width 10))`.
+#[derive(Debug, Default)]
+pub struct Parameters {
+    pub required: Vec<Symbol>,
+    /// Parameters with the expression computing them when they are missing.
+    pub optional: Vec<(Symbol, Datum)>,
+    /// Bound to the list of the arguments after the positional ones.
+    pub rest: Option<Symbol>,
+    /// Parameters passed as `name: value` after the positional ones.
+    pub keys: Vec<(Symbol, Datum)>,
+}
+
+impl Parameters {
+    pub fn arity(&self) -> Arity {
+        let unbounded = self.rest.is_some() || !self.keys.is_empty();
+        (
+            self.required.len(),
+            (!unbounded).then_some(self.required.len() + self.optional.len()),
+        )
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        let (min, max) = self.arity();
+        count >= min && max.is_none_or(|max| count <= max)
+    }
+}
+
+/// The smallest and, unless there is none, the largest number of arguments
+/// a procedure takes.
+pub type Arity = (usize, Option<usize>);
+
+#[derive(Debug)]
+pub struct Lambda {
+    /// The name it was first defined as, for error messages.
+    pub name: Cell<Option<Symbol>>,
+    pub parameters: Parameters,
+    pub env: Env,
+    pub body: Rc<[Datum]>,
+}
+
+/// Runtime value produced by `eval`. Every variant is either plain data or a
+/// reference counted pointer, so cloning a value is O(1) no matter how large
+/// the list or lambda body behind it is.
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// Mutable, `string-set!` changes it for everyone holding it.
+    Str(Rc<RefCell<String>>),
+    Symbol(Symbol),
+    Float(f64),
+    Int(i128),
+    Bool(bool),
+    Char(char),
+    Cons {
+        head: Rc<Value>,
+        tail: Rc<Value>,
+    },
+    Nil,
+    Vector(Rc<RefCell<Vec<Value>>>),
+    Bytevector(Rc<RefCell<Vec<u8>>>),
+    Lambda(Rc<Lambda>),
+    /// Procedure that applies the first of its lambdas that accepts the
+    /// number of arguments.
+    CaseLambda(Rc<[Rc<Lambda>]>),
+    /// Self evaluating `name:`, passes the argument after it to the
+    /// parameter `name`.
+    Keyword(Symbol),
+    Operation(Operation),
+    Continuation(Rc<Continuation>),
+    ErrorObject(Rc<ErrorObject>),
+    Macro(Rc<Macro>),
+    Native(Rc<Native>),
+    Regexp(Rc<Regex>),
+    Port(Rc<Port>),
+    /// What reading past the end of a port returns.
+    Eof,
+    /// What the second argument of `eval` evaluates to.
+    Environment(Env),
+    /// What an internal `define` binds until its value is computed.
+    Unassigned,
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Str(left), Self::Str(right)) => left == right,
+            (Self::Symbol(left), Self::Symbol(right)) => left == right,
+            (Self::Float(left), Self::Float(right)) => left == right,
+            (Self::Int(left), Self::Int(right)) => left == right,
+            (Self::Bool(left), Self::Bool(right)) => left == right,
+            (Self::Char(left), Self::Char(right)) => left == right,
+            (Self::Vector(left), Self::Vector(right)) => {
+                Rc::ptr_eq(left, right) || *left.borrow() == *right.borrow()
+            }
+            (Self::Bytevector(left), Self::Bytevector(right)) => left == right,
+            (Self::Cons { .. }, Self::Cons { .. }) => lists_equal(self, other),
+            (Self::Nil, Self::Nil) => true,
+            (Self::Unassigned, Self::Unassigned) => true,
+            (Self::Lambda(left), Self::Lambda(right)) => Rc::ptr_eq(left, right),
+            (Self::CaseLambda(left), Self::CaseLambda(right)) => Rc::ptr_eq(left, right),
+            (Self::Keyword(left), Self::Keyword(right)) => left == right,
+            (Self::Operation(left), Self::Operation(right)) => left == right,
+            (Self::Continuation(left), Self::Continuation(right)) => Rc::ptr_eq(left, right),
+            (Self::ErrorObject(left), Self::ErrorObject(right)) => Rc::ptr_eq(left, right),
+            (Self::Macro(left), Self::Macro(right)) => Rc::ptr_eq(left, right),
+            (Self::Native(left), Self::Native(right)) => Rc::ptr_eq(left, right),
+            (Self::Regexp(left), Self::Regexp(right)) => left.as_str() == right.as_str(),
+            (Self::Port(left), Self::Port(right)) => Rc::ptr_eq(left, right),
+            (Self::Eof, Self::Eof) => true,
+            (Self::Environment(left), Self::Environment(right)) => left.ptr_eq(right),
+            _ => false,
+        }
+    }
+}
+
+/// Compares two lists a pair at a time, so that long lists don't overflow
+/// the stack the way recursing into every tail would.
+fn lists_equal(mut left: &Value, mut right: &Value) -> bool {
+    loop {
+        match (left, right) {
+            (
+                Value::Cons { head, tail },
+                Value::Cons {
+                    head: other_head,
+                    tail: other_tail,
+                },
+            ) => {
+                if !(Rc::ptr_eq(head, other_head) || head == other_head) {
+                    return false;
+                }
+                if Rc::ptr_eq(tail, other_tail) {
+                    return true;
+                }
+                left = tail;
+                right = other_tail;
+            }
+            (left, right) => return left == right,
+        }
+    }
+}
+
+thread_local! {
+    static NIL: Rc<Value> = Rc::new(Value::Nil);
+}
+
+impl Drop for Value {
+    /// Frees the heads and tails of a list from a stack of its own instead of
+    /// each dropping the next, which would overflow the stack on long or
+    /// deeply nested lists.
+    fn drop(&mut self) {
+        let Value::Cons { head, tail } = self else {
+            return;
+        };
+        let mut pending: Vec<Rc<Value>> = [head, tail].into_iter().filter_map(take_list).collect();
+        while let Some(next) = pending.pop() {
+            if let Ok(Value::Cons { head, tail }) = Rc::try_unwrap(next).as_mut() {
+                pending.extend([head, tail].into_iter().filter_map(take_list));
+            }
+        }
+    }
+}
+
+/// Takes a list out of `value` if nothing else holds on to it, so that its
+/// drop can be deferred.
+fn take_list(value: &mut Rc<Value>) -> Option<Rc<Value>> {
+    if Rc::strong_count(value) > 1 || !matches!(**value, Value::Cons { .. }) {
+        return None;
+    }
+    Some(std::mem::replace(value, NIL.with(Rc::clone)))
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Str(left), Self::Str(right)) => left.partial_cmp(right),
+            (Self::Char(left), Self::Char(right)) => left.partial_cmp(right),
+            (Self::Float(left), Self::Float(right)) => left.partial_cmp(right),
+            (Self::Int(left), Self::Int(right)) => left.partial_cmp(right),
+            (Self::Int(left), Self::Float(right)) => (*left as f64).partial_cmp(right),
+            (Self::Float(left), Self::Int(right)) => left.partial_cmp(&(*right as f64)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Float(value) => write!(f, "{}", value),
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Str(value) => write!(f, "{}", value.borrow()),
+            Self::Cons { .. } => {
+                // a loop instead of recursing into the tail, which would
+                // overflow the stack on long lists
+                let mut depth = 0;
+                let mut current = self;
+                while let Self::Cons { head, tail } = current {
+                    write!(f, "({} . ", head)?;
+                    depth += 1;
+                    current = tail;
+                }
+                write!(f, "{}{}", current, ")".repeat(depth))
+            }
+            Self::Symbol(value) => write!(f, "#{}", value),
+            Self::Bool(value) => write!(f, "{}", if *value { "#t" } else { "#f" }),
+            Self::Nil => write!(f, "#nil"),
+            Self::Char(value) => write!(f, "{}", value),
+            Self::Vector(values) => {
+                write!(f, "#(")?;
+                for (index, value) in values.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, ")")
+            }
+            Self::Bytevector(bytes) => {
+                write!(f, "#u8(")?;
+                for (index, byte) in bytes.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", byte)?;
+                }
+                write!(f, ")")
+            }
+            Self::Lambda(lambda) => {
+                write!(f, "(lambda ({:?}) ({:?}))", lambda.parameters, lambda.body)
+            }
+            Self::CaseLambda(_) => write!(f, "#<case-lambda>"),
+            Self::Keyword(name) => write!(f, "{}:", name),
+            Self::Operation(operation) => write!(f, "#<procedure {}>", operation.name()),
+            Self::Continuation(_) => write!(f, "#<continuation>"),
+            Self::ErrorObject(error_object) => write!(f, "#<error {}>", error_object),
+            Self::Macro(_) => write!(f, "#<macro>"),
+            Self::Native(native) => write!(f, "#<procedure {}>", native.name),
+            Self::Regexp(regex) => write!(f, "#<regexp {}>", regex.as_str()),
+            Self::Port(_) => write!(f, "#<port>"),
+            Self::Eof => write!(f, "#<eof>"),
+            Self::Environment(_) => write!(f, "#<environment>"),
+            Self::Unassigned => write!(f, "#<unassigned>"),
+        }
+    }
+}
+
+impl From<&Datum> for Value {
+    /// Converts quoted syntax into data: lists become chains of cons cells
+    /// and symbols stay symbols, even when they name an operation.
+    fn from(datum: &Datum) -> Self {
+        match datum {
+            Datum::Str(value) => Value::string(value.as_ref()),
+            Datum::Symbol(value) => Value::Symbol(*value),
+            Datum::Float(value) => Value::Float(*value),
+            Datum::Int(value) => Value::Int(*value),
+            Datum::Bool(value) => Value::Bool(*value),
+            Datum::Char(value) => Value::Char(*value),
+            Datum::Bytevector(bytes) => Value::bytevector(bytes.to_vec()),
+            Datum::Nil => Value::Nil,
+            Datum::Keyword(name) => Value::Keyword(*name),
+            Datum::List(values) => Value::list(values.iter().map(Value::from)),
+        }
+    }
+}
+
+impl Value {
+    pub fn native(
+        name: &'static str,
+        function: impl Fn(&[Value]) -> Result<Value> + 'static,
+    ) -> Value {
+        Value::Native(Rc::new(Native {
+            name,
+            function: Box::new(function),
+        }))
+    }
+
+    /// Builds a proper list of `values`.
+    pub fn list(values: impl DoubleEndedIterator<Item = Value>) -> Value {
+        values.rfold(Value::Nil, |tail, head| Value::Cons {
+            head: Rc::new(head),
+            tail: Rc::new(tail),
+        })
+    }
+
+    /// Everything but `#f` counts as true.
+    pub fn is_true(&self) -> bool {
+        !matches!(self, Value::Bool(false))
+    }
+
+    /// Whether two values are the same object, what `memv` and `assv`
+    /// compare with: atoms by value, anything with contents by identity.
+    /// Numbers and characters aren't boxed, so `memq` and `assq` compare
+    /// the same way.
+    pub fn is_eqv(&self, other: &Value) -> bool {
+        match (self, other) {
+            (
+                Value::Cons { head, tail },
+                Value::Cons {
+                    head: other_head,
+                    tail: other_tail,
+                },
+            ) => Rc::ptr_eq(head, other_head) && Rc::ptr_eq(tail, other_tail),
+            (Value::Str(left), Value::Str(right)) => Rc::ptr_eq(left, right),
+            (Value::Vector(left), Value::Vector(right)) => Rc::ptr_eq(left, right),
+            (Value::Bytevector(left), Value::Bytevector(right)) => Rc::ptr_eq(left, right),
+            (Value::Regexp(left), Value::Regexp(right)) => Rc::ptr_eq(left, right),
+            (left, right) => left == right,
+        }
+    }
+
+    /// The elements of a proper list.
+    pub fn to_vec(&self) -> Result<Vec<Value>> {
+        let mut values = Vec::new();
+        let mut current = self;
+        while let Value::Cons { head, tail } = current {
+            values.push(head.as_ref().clone());
+            current = tail;
+        }
+        match current {
+            Value::Nil => Ok(values),
+            _ => Err(InterpreterError::ValueError(format!(
+                "{} is not a proper list",
+                self
+            ))),
+        }
+    }
+
+    pub fn string(string: impl Into<String>) -> Value {
+        Value::Str(Rc::new(RefCell::new(string.into())))
+    }
+
+    pub fn vector(values: Vec<Value>) -> Value {
+        Value::Vector(Rc::new(RefCell::new(values)))
+    }
+
+    pub fn bytevector(bytes: Vec<u8>) -> Value {
+        Value::Bytevector(Rc::new(RefCell::new(bytes)))
+    }
+
+    /// Converts data back into syntax so it can be evaluated. Only proper
+    /// lists and atoms have a syntactic representation, and lists only up
+    /// to the depth code may have.
+    pub fn to_datum(&self) -> Result<Datum> {
+        self.to_datum_within(MAX_DEPTH)
+    }
+
+    fn to_datum_within(&self, depth: usize) -> Result<Datum> {
+        match self {
+            Value::Str(value) => Ok(Datum::Str(value.borrow().as_str().into())),
+            Value::Symbol(value) => Ok(Datum::Symbol(*value)),
+            Value::Float(value) => Ok(Datum::Float(*value)),
+            Value::Int(value) => Ok(Datum::Int(*value)),
+            Value::Bool(value) => Ok(Datum::Bool(*value)),
+            Value::Char(value) => Ok(Datum::Char(*value)),
+            Value::Bytevector(bytes) => Ok(Datum::Bytevector(bytes.borrow().as_slice().into())),
+            Value::Nil => Ok(Datum::Nil),
+            Value::Keyword(name) => Ok(Datum::Keyword(*name)),
+            Value::Cons { .. } if depth == 0 => Err(InterpreterError::ValueError(format!(
+                "cannot evaluate lists nested more than {} deep",
+                MAX_DEPTH
+            ))),
+            Value::Cons { .. } => {
+                let mut values = Vec::new();
+                let mut current = self;
+                while let Value::Cons { head, tail } = current {
+                    values.push(head.to_datum_within(depth - 1)?);
+                    current = tail;
+                }
+                match current {
+                    Value::Nil => Ok(Datum::List(values.into())),
+                    _ => Err(InterpreterError::ValueError(format!(
+                        "cannot evaluate improper list {}",
+                        self
+                    ))),
+                }
+            }
+            Value::Vector(_)
+            | Value::Lambda(_)
+            | Value::CaseLambda(_)
+            | Value::Operation(_)
+            | Value::Continuation(_)
+            | Value::ErrorObject(_)
+            | Value::Macro(_)
+            | Value::Native(_)
+            | Value::Regexp(_)
+            | Value::Port(_)
+            | Value::Eof
+            | Value::Environment(_)
+            | Value::Unassigned => Err(InterpreterError::ValueError(format!(
+                "{} has no syntactic representation",
+                self
+            ))),
+        }
+    }
+}
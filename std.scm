@@ -1,25 +1,38 @@
 (module
-    (define fib (lambda (n) (cond ((< n 2) 1) (#t (+ (fib (- n 1)) (fib (- n 2)))))))
-    (define fact (lambda (n) (if (<= n 1) 1 (* n (fact (- n 1))))))
-    (define range (lambda (n) (cond ((= n 0) #nil)(#t (cons n (range (- n 1)))))))
-    (define null? (lambda (x) (= x #nil)))
-    (define map (lambda (func l) (if (null? l) #nil (cons (func (car l)) (map func (cdr l))))))
-    (define mapi (lambda (func l)
-        (let ((map-iter (lambda (acc rest)
-                (if (null? rest)
-                    acc
-                    (map-iter (cons (func (car rest)) acc) (cdr rest))))))
-            (map-iter #nil l))))
-    (define reduce (lambda (func l) (if (null? (cdr l)) (car l) (func (car l) (reduce func (cdr l))))))
-    (define reducei (lambda (func l)
-        (let ((reduce-iter (lambda (acc rest)
-                (if (null? rest)
-                    acc
-                    (reduce-iter (func acc (car rest)) (cdr rest))))))
-        (reduce-iter (car l) (cdr l)))))
-    (define make-account
-      (lambda (balance)
+    (define-syntax and (syntax-rules ()
+        ((and) #t)
+        ((and test) test)
+        ((and test rest ...) (if test (and rest ...) #f))))
+    (define-syntax or (syntax-rules ()
+        ((or) #f)
+        ((or test) test)
+        ((or test rest ...) (let ((value test)) (if value value (or rest ...))))))
+    (define-syntax when (syntax-rules ()
+        ((when test body ...) (if test (begin body ...)))))
+    (define-syntax unless (syntax-rules ()
+        ((unless test body ...) (if test #nil (begin body ...)))))
+    (define (fib n) (cond ((< n 2) 1) (#t (+ (fib (- n 1)) (fib (- n 2))))))
+    (define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))
+    (define (range n) (cond ((= n 0) #nil)(#t (cons n (range (- n 1))))))
+    (define (null? x) (= x #nil))
+    (define (map-recursive func l)
+        (if (null? l) #nil (cons (func (car l)) (map-recursive func (cdr l)))))
+    (define (reduce-recursive func l)
+        (if (null? (cdr l)) (car l) (func (car l) (reduce-recursive func (cdr l)))))
+    (define (mapi func l)
+        (define (map-iter acc rest)
+            (if (null? rest)
+                acc
+                (map-iter (cons (func (car rest)) acc) (cdr rest))))
+        (map-iter #nil l))
+    (define (reducei func l)
+        (define (reduce-iter acc rest)
+            (if (null? rest)
+                acc
+                (reduce-iter (func acc (car rest)) (cdr rest))))
+        (reduce-iter (car l) (cdr l)))
+    (define (make-account balance)
         (lambda (amt)
-            (begin (set! balance (+ balance amt))
-                    balance))))
+            (set! balance (+ balance amt))
+            balance))
 )